@@ -0,0 +1,235 @@
+use crate::{
+    lang,
+    permalink::{Field, Template},
+    Article, PageMeta, DATE_FORMAT,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use time::Date;
+
+fn url(key: &[String]) -> String {
+    format!("/{}/", key.join("/"))
+}
+
+/// URLs of the archive pages listing articles published on `dates`.
+pub fn urls(template: &Template, dates: impl IntoIterator<Item = Date>) -> BTreeSet<String> {
+    let mut urls = BTreeSet::from(["/archive/".to_string()]);
+    for date in dates {
+        let values = template.archive_values(date);
+        urls.extend((1..=values.len()).map(|depth| url(&values[..depth])));
+    }
+    urls
+}
+
+/// Articles grouped by period of the permalink hierarchy, such as years or
+/// weeks of a year.
+type Periods<'a> = BTreeMap<Vec<String>, Vec<&'a Article>>;
+
+/// A period of the archive, such as a year or a week of a year.
+pub struct Period<'a> {
+    /// Short name of the period, such as `2022`, `March` or `Week 14`.
+    pub title: String,
+    /// The page listing the period on its own, when it has one.
+    pub url: Option<String>,
+    /// Articles published during the period, oldest first.
+    pub articles: Vec<&'a Article>,
+    /// Shorter periods within this one, oldest first. Only given for the
+    /// years of the archive index and for the period a page is about.
+    pub children: Vec<Period<'a>>,
+}
+
+/// The page of a period. Pages of the shortest periods have no children
+/// and show their articles instead.
+pub struct PeriodPage<'a> {
+    pub period: Period<'a>,
+    /// URL and name of the enclosing period, or of the archive index.
+    pub parent: (String, String),
+    /// URL and full name of the neighbouring periods of the same length.
+    pub prev: Option<(String, String)>,
+    pub next: Option<(String, String)>,
+}
+
+pub struct Archive<'a> {
+    fields: Vec<Field>,
+    articles: &'a [Article],
+    template: &'a Template,
+    /// Language month names are given in.
+    lang: &'a str,
+}
+
+impl<'a> Archive<'a> {
+    pub fn new(articles: &'a [Article], template: &'a Template, lang: &'a str) -> Self {
+        Archive {
+            fields: template.archive_fields(),
+            articles,
+            template,
+            lang,
+        }
+    }
+
+    fn periods(&self, depth: usize) -> Periods<'a> {
+        let mut periods: Periods<'a> = BTreeMap::new();
+        for article in self.articles {
+            let mut key = self.template.archive_values(article.date);
+            key.truncate(depth);
+            periods.entry(key).or_default().push(article);
+        }
+        periods
+    }
+
+    /// Short name of a period, shown under its parent period.
+    fn title(&self, key: &[String], article: &Article) -> String {
+        match self.fields[key.len() - 1] {
+            Field::Year => key[0].clone(),
+            Field::Month => lang::month(article.date.month(), self.lang).to_string(),
+            Field::Week => format!("Week {}", key[key.len() - 1].trim_start_matches('0')),
+            _ => article.date.format(&DATE_FORMAT).unwrap(),
+        }
+    }
+
+    /// Full name of a period, used when linking to neighbouring periods.
+    fn label(&self, key: &[String], article: &Article) -> String {
+        match self.fields[key.len() - 1] {
+            Field::Year => key[0].clone(),
+            Field::Month => format!(
+                "{} {}",
+                lang::month(article.date.month(), self.lang),
+                key[0]
+            ),
+            Field::Week => format!("{}, {}", key[0], self.title(key, article).to_lowercase()),
+            _ => article.date.format(&DATE_FORMAT).unwrap(),
+        }
+    }
+
+    /// Old archive URLs, from before week-based years were used, along with
+    /// the period they moved to.
+    pub fn redirects(&self) -> BTreeMap<String, String> {
+        let mut redirects = BTreeMap::new();
+        for article in self.articles {
+            let legacy = self.template.legacy_archive_values(article.date);
+            let current = self.template.archive_values(article.date);
+            for depth in 1..=self.fields.len() {
+                if legacy[..depth] != current[..depth] {
+                    redirects
+                        .entry(url(&legacy[..depth]))
+                        .or_insert_with(|| url(&current[..depth]));
+                }
+            }
+        }
+        redirects
+    }
+
+    /// The years of the archive index, with their articles grouped by
+    /// calendar month.
+    pub fn years(&self) -> Vec<Period<'a>> {
+        let mut years: BTreeMap<i32, BTreeMap<u8, Vec<&Article>>> = BTreeMap::new();
+        for article in self.articles {
+            years
+                .entry(article.date.year())
+                .or_default()
+                .entry(article.date.month() as u8)
+                .or_default()
+                .push(article);
+        }
+        let year_pages = self.periods(1);
+
+        years
+            .into_iter()
+            .map(|(year, months)| {
+                let months: Vec<Period> = months
+                    .into_values()
+                    .map(|articles| Period {
+                        title: lang::month(articles[0].date.month(), self.lang).to_string(),
+                        url: None,
+                        articles,
+                        children: Vec::new(),
+                    })
+                    .collect();
+                // Archive years are week-based when permalinks use weeks, so
+                // a calendar year may have no page of its own.
+                let key = [format!("{:04}", year)];
+                Period {
+                    title: year.to_string(),
+                    url: year_pages.contains_key(&key[..]).then(|| url(&key)),
+                    articles: months
+                        .iter()
+                        .flat_map(|month| month.articles.iter().copied())
+                        .collect(),
+                    children: months,
+                }
+            })
+            .collect()
+    }
+
+    pub fn pages(&self) -> Vec<(String, PageMeta, PeriodPage<'a>)> {
+        let mut pages = Vec::new();
+
+        for depth in 1..=self.fields.len() {
+            let periods = self.periods(depth);
+            let keys: Vec<_> = periods.keys().collect();
+            let link = |key: &[String]| (url(key), self.label(key, periods[key][0]));
+
+            for (index, (key, articles)) in periods.iter().enumerate() {
+                let prev = index.checked_sub(1).map(|index| link(keys[index]));
+                let next = keys.get(index + 1).map(|key| link(key));
+                pages.push((
+                    url(key),
+                    meta(&prev, &next),
+                    self.period_page(key, articles, prev, next),
+                ));
+            }
+        }
+
+        pages
+    }
+
+    fn period_page(
+        &self,
+        key: &[String],
+        articles: &[&'a Article],
+        prev: Option<(String, String)>,
+        next: Option<(String, String)>,
+    ) -> PeriodPage<'a> {
+        let parent = match key.len() {
+            1 => ("/archive/".to_string(), "Archive".to_string()),
+            n => (url(&key[..n - 1]), self.title(&key[..n - 1], articles[0])),
+        };
+
+        let mut children: Periods = BTreeMap::new();
+        if key.len() < self.fields.len() {
+            for &article in articles {
+                let mut child = self.template.archive_values(article.date);
+                child.truncate(key.len() + 1);
+                children.entry(child).or_default().push(article);
+            }
+        }
+        let children = children
+            .into_iter()
+            .map(|(child, articles)| Period {
+                title: self.title(&child, articles[0]),
+                url: Some(url(&child)),
+                articles,
+                children: Vec::new(),
+            })
+            .collect();
+
+        PeriodPage {
+            period: Period {
+                title: self.title(key, articles[0]),
+                url: Some(url(key)),
+                articles: articles.to_vec(),
+                children,
+            },
+            parent,
+            prev,
+            next,
+        }
+    }
+}
+
+fn meta(prev: &Option<(String, String)>, next: &Option<(String, String)>) -> PageMeta {
+    PageMeta {
+        prev: prev.as_ref().map(|(url, _)| url.clone()),
+        next: next.as_ref().map(|(url, _)| url.clone()),
+        ..PageMeta::default()
+    }
+}
@@ -0,0 +1,310 @@
+use crate::{cache, cache::Cache, css, BlogData};
+use anyhow::{bail, Context};
+use glob::Pattern;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Where assets end up in the output, and so their URL prefix.
+const TARGET: &str = "assets";
+
+/// The icon sprite referenced by social links.
+pub const ICONS: &str = "/assets/icons.svg";
+
+/// Lists fingerprinted names, relative to `/assets/`. Named after what it
+/// holds rather than `manifest.json`, which sites use for their web app
+/// manifest.
+const MANIFEST: &str = "fingerprints.json";
+
+/// Name of bundled stylesheets, written to `/assets/bundle.css`, then to
+/// `/assets/bundle-2.css` and so on when there are several.
+const BUNDLE: &str = "bundle";
+
+/// Hex digits of the content hash kept in fingerprinted names.
+const FINGERPRINT_LENGTH: usize = 8;
+
+#[derive(serde::Deserialize)]
+#[serde(default)]
+pub struct Assets {
+    /// Directory copied to `/assets/`, relative to the site root.
+    pub dir: PathBuf,
+    /// Glob patterns of files to leave out, matched against both the path
+    /// relative to `dir` and the file name.
+    pub ignore: Vec<String>,
+    /// Also write each asset under a name containing a hash of its contents,
+    /// such as `style.3f2a9c1d.css`, and link to that copy instead. The
+    /// original names are kept, so that references between assets still
+    /// work.
+    pub fingerprint: bool,
+    /// Replace the local stylesheets by a single minified one, inlining
+    /// their `@import`s.
+    pub bundle: bool,
+}
+
+pub struct Asset {
+    path: PathBuf,
+    contents: Vec<u8>,
+    fingerprinted: Option<PathBuf>,
+}
+
+impl Asset {
+    fn new(path: PathBuf, contents: Vec<u8>, fingerprint: bool) -> Self {
+        Asset {
+            fingerprinted: fingerprint.then(|| fingerprinted(&path, &contents)),
+            path,
+            contents,
+        }
+    }
+}
+
+fn fingerprinted(path: &Path, contents: &[u8]) -> PathBuf {
+    let hash = &cache::hash(contents)[..FINGERPRINT_LENGTH];
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, hash, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, hash),
+    };
+    path.with_file_name(name)
+}
+
+fn url(path: &Path) -> String {
+    format!("/{}/{}", TARGET, path.to_string_lossy().replace('\\', "/"))
+}
+
+impl Default for Assets {
+    fn default() -> Self {
+        Assets {
+            dir: PathBuf::from("assets"),
+            ignore: vec![".*".to_string()],
+            fingerprint: false,
+            bundle: false,
+        }
+    }
+}
+
+impl Assets {
+    fn patterns(&self) -> anyhow::Result<Vec<Pattern>> {
+        self.ignore
+            .iter()
+            .map(|pattern| {
+                Pattern::new(pattern)
+                    .with_context(|| format!("invalid assets.ignore pattern `{}`", pattern))
+            })
+            .collect()
+    }
+
+    /// Asset files, relative to the assets directory.
+    fn files(&self, root: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        let dir = root.join(&self.dir);
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let ignore = self.patterns()?;
+        let ignored = |path: &Path| {
+            ignore.iter().any(|pattern| {
+                pattern.matches_path(path)
+                    || path
+                        .file_name()
+                        .into_iter()
+                        .any(|name| pattern.matches_path(Path::new(name)))
+            })
+        };
+
+        let mut files = Vec::new();
+        walk(&dir, Path::new(""), &ignored, &mut files)?;
+        files.sort();
+        Ok(files)
+    }
+
+    /// Reads the assets, fingerprinting them if enabled.
+    pub fn load(&self, root: &Path) -> anyhow::Result<Vec<Asset>> {
+        let files = self.files(root)?;
+        if self.fingerprint && files.iter().any(|path| path == Path::new(MANIFEST)) {
+            bail!(
+                "{} is reserved for the fingerprint manifest, rename it",
+                self.dir.join(MANIFEST).display()
+            );
+        }
+
+        files
+            .into_iter()
+            .map(|path| {
+                let source = root.join(&self.dir).join(&path);
+                let contents = fs::read(&source)
+                    .with_context(|| format!("failed to read {}", source.display()))?;
+                Ok(Asset::new(path, contents, self.fingerprint))
+            })
+            .collect()
+    }
+
+    /// Bundles the local stylesheets into new assets and returns the
+    /// resulting list of stylesheets. External ones are kept as they are,
+    /// and local ones after them go into another bundle, so that the
+    /// stylesheets still apply in the order they are listed in.
+    ///
+    /// Local stylesheets importing external ones are kept as they are too,
+    /// as their `@import`s would otherwise have to move before the rules of
+    /// the stylesheets bundled ahead of them.
+    pub fn bundle(
+        &self,
+        assets: &mut Vec<Asset>,
+        stylesheets: &[String],
+    ) -> anyhow::Result<Vec<String>> {
+        let read = |url: &str| {
+            assets
+                .iter()
+                .find(|asset| self::url(&asset.path) == url)
+                .map(|asset| asset.contents.as_slice())
+        };
+        let mut bundleable = HashSet::new();
+        for stylesheet in stylesheets.iter().filter(|stylesheet| is_local(stylesheet)) {
+            let mut bundler = css::Bundler::new(&read);
+            bundler.add(stylesheet)?;
+            if !bundler.imports_external() {
+                bundleable.insert(stylesheet);
+            }
+        }
+
+        let mut groups: Vec<Vec<&str>> = Vec::new();
+        let mut bundled = Vec::new();
+        let mut grouping = false;
+        for stylesheet in stylesheets {
+            grouping = match (bundleable.contains(stylesheet), grouping) {
+                (false, _) => {
+                    bundled.push(stylesheet.clone());
+                    false
+                }
+                (true, false) => {
+                    groups.push(vec![stylesheet]);
+                    bundled.push(url(&bundle_path(groups.len())));
+                    true
+                }
+                (true, true) => {
+                    groups.last_mut().unwrap().push(stylesheet);
+                    true
+                }
+            };
+        }
+
+        for number in 1..=groups.len() {
+            let path = bundle_path(number);
+            if assets.iter().any(|asset| asset.path == path) {
+                bail!(
+                    "{} is reserved for bundled stylesheets, rename it",
+                    self.dir.join(path).display()
+                );
+            }
+        }
+
+        let mut bundles = Vec::new();
+        for group in &groups {
+            let mut bundler = css::Bundler::new(&read);
+            for stylesheet in group {
+                bundler.add(stylesheet)?;
+            }
+            bundles.push(css::minify(&bundler.finish()).into_bytes());
+        }
+
+        for (index, contents) in bundles.into_iter().enumerate() {
+            let path = bundle_path(index + 1);
+            assets.push(Asset::new(path, contents, self.fingerprint));
+        }
+        Ok(bundled)
+    }
+}
+
+fn bundle_path(number: usize) -> PathBuf {
+    match number {
+        1 => PathBuf::from(format!("{}.css", BUNDLE)),
+        number => PathBuf::from(format!("{}-{}.css", BUNDLE, number)),
+    }
+}
+
+/// Collects the files below `dir`, relative to the assets directory, leaving
+/// out ignored ones along with everything in ignored directories.
+fn walk(
+    dir: &Path,
+    relative: &Path,
+    ignored: &dyn Fn(&Path) -> bool,
+    files: &mut Vec<PathBuf>,
+) -> anyhow::Result<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?;
+    for entry in entries {
+        let entry = entry.with_context(|| format!("failed to read {}", dir.display()))?;
+        let relative = relative.join(entry.file_name());
+        if ignored(&relative) {
+            continue;
+        }
+
+        let path = entry.path();
+        if path.is_dir() {
+            walk(&path, &relative, ignored, files)?;
+        } else if path.is_file() {
+            files.push(relative);
+        }
+    }
+    Ok(())
+}
+
+/// Maps asset URLs to their fingerprinted version.
+pub fn urls(assets: &[Asset]) -> BTreeMap<String, String> {
+    assets
+        .iter()
+        .filter_map(|asset| Some((url(&asset.path), url(asset.fingerprinted.as_ref()?))))
+        .collect()
+}
+
+/// Writes the assets into the output directory, along with the manifest
+/// when fingerprinting.
+pub(crate) fn copy(assets: &[Asset], cache: &mut Cache) -> anyhow::Result<()> {
+    let target = Path::new(TARGET);
+    let mut manifest = BTreeMap::new();
+    for asset in assets {
+        cache.write(&target.join(&asset.path), &asset.contents)?;
+        if let Some(fingerprinted) = &asset.fingerprinted {
+            cache.write(&target.join(fingerprinted), &asset.contents)?;
+            manifest.insert(&asset.path, fingerprinted);
+        }
+    }
+
+    if !manifest.is_empty() {
+        cache.write(
+            &target.join(MANIFEST),
+            serde_json::to_string_pretty(&manifest)?,
+        )?;
+    }
+    Ok(())
+}
+
+fn is_local(url: &str) -> bool {
+    url.starts_with('/') && !url.starts_with("//")
+}
+
+/// Fails unless every local file the layout links to was generated.
+pub(crate) fn check(blog_data: &BlogData, cache: &Cache) -> anyhow::Result<()> {
+    let icons = (!blog_data.socials.is_empty()).then_some(ICONS);
+    let missing: Vec<&str> = blog_data
+        .stylesheets
+        .iter()
+        .map(String::as_str)
+        .chain(icons)
+        .filter(|url| is_local(url))
+        .filter(|url| {
+            let url = blog_data.asset_url(url);
+            let path = url.split(['?', '#']).next().unwrap_or_default();
+            !cache.exists(Path::new(path.trim_start_matches('/')))
+        })
+        .collect();
+
+    if !missing.is_empty() {
+        bail!(
+            "missing assets, add them to the {} directory: {}",
+            blog_data.assets.dir.display(),
+            missing.join(", ")
+        );
+    }
+    Ok(())
+}
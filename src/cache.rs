@@ -0,0 +1,456 @@
+use crate::{feed::Entry, minify, HtmlDocument, SiteArgs, DATE_FORMAT};
+use dolmen::Fragment;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+const FILE_NAME: &str = ".build-cache.json";
+
+pub fn hash(data: impl AsRef<[u8]>) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Where the page at `url` is written, relative to the output directory.
+fn index_path(url: &str) -> PathBuf {
+    Path::new(url.trim_matches('/')).join("index.html")
+}
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct State {
+    version: String,
+    config: String,
+    #[serde(default)]
+    assets: String,
+    options: String,
+    listings: String,
+    sources: BTreeMap<PathBuf, Source>,
+    outputs: BTreeMap<PathBuf, String>,
+    #[serde(default)]
+    legacy: BTreeMap<String, String>,
+    /// Pages generated from the listings rather than from a single source.
+    #[serde(default)]
+    listed: BTreeSet<PathBuf>,
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+struct Source {
+    hash: String,
+    output: Option<String>,
+    entry: Option<Entry>,
+    #[serde(default)]
+    scheduled: Option<String>,
+    #[serde(default)]
+    aliases: Vec<String>,
+}
+
+/// Tracks source and output hashes between builds, so that unchanged
+/// sources are neither parsed nor rendered and unchanged files are not
+/// written again.
+pub struct Cache {
+    output_dir: PathBuf,
+    today: String,
+    previous: State,
+    current: State,
+    /// Files written by the last build, even when its cache is not reused.
+    generated: BTreeSet<PathBuf>,
+    touched: HashSet<PathBuf>,
+    /// Generated files, when they are kept in memory instead of written.
+    files: Option<BTreeMap<PathBuf, Vec<u8>>>,
+    /// Whether HTML is minified before being written.
+    pub minify: bool,
+    pub written: usize,
+    pub unchanged: usize,
+    /// Files of previous builds removed because nothing generates them
+    /// anymore.
+    pub removed: usize,
+    /// Bytes removed from HTML by minification.
+    pub saved: usize,
+}
+
+impl Cache {
+    /// Pages embed asset URLs, so they are rendered again whenever a
+    /// fingerprinted asset changes, just like when the configuration does.
+    ///
+    /// A `dry_run` cache starts from scratch and keeps everything in memory,
+    /// leaving the output directory alone.
+    pub fn load(
+        site: &SiteArgs,
+        config: &str,
+        assets: &BTreeMap<String, String>,
+        dry_run: bool,
+    ) -> anyhow::Result<Self> {
+        let output_dir = site.output.as_path();
+        if !dry_run && !output_dir.is_dir() {
+            fs::create_dir_all(output_dir)?;
+        }
+
+        let current = State {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            config: hash(config),
+            assets: hash(serde_json::to_vec(assets)?),
+            options: site.options(),
+            ..State::default()
+        };
+
+        // While building, the cache file only lists the files written by
+        // earlier builds, and is written back in full once the build
+        // succeeds. An interrupted build then starts over, but the next one
+        // still knows what to clean up.
+        let path = output_dir.join(FILE_NAME);
+        let saved = fs::read(&path)
+            .ok()
+            .filter(|_| !dry_run)
+            .and_then(|contents| serde_json::from_slice::<State>(&contents).ok());
+        let generated: BTreeSet<PathBuf> = saved
+            .iter()
+            .flat_map(|saved| saved.outputs.keys().cloned())
+            .collect();
+        let previous = saved
+            .filter(|previous| {
+                !site.force
+                    && previous.version == current.version
+                    && previous.config == current.config
+                    && previous.assets == current.assets
+                    && previous.options == current.options
+            })
+            .unwrap_or_default();
+        if !dry_run && path.exists() {
+            let incomplete = State {
+                outputs: generated
+                    .iter()
+                    .map(|path| (path.clone(), String::new()))
+                    .collect(),
+                ..State::default()
+            };
+            fs::write(&path, serde_json::to_vec(&incomplete)?)?;
+        }
+
+        Ok(Cache {
+            output_dir: output_dir.to_path_buf(),
+            today: site.today().format(&DATE_FORMAT)?,
+            current: State {
+                outputs: previous.outputs.clone(),
+                legacy: previous.legacy.clone(),
+                listed: previous.listed.clone(),
+                ..current
+            },
+            previous,
+            generated,
+            touched: HashSet::new(),
+            files: dry_run.then(BTreeMap::new),
+            minify: false,
+            written: 0,
+            unchanged: 0,
+            removed: 0,
+            saved: 0,
+        })
+    }
+
+    /// Hashes a source file and returns whether it has to be processed again.
+    pub fn check(&mut self, path: &Path) -> anyhow::Result<bool> {
+        let source_hash = hash(fs::read(path)?);
+        let source = self
+            .previous
+            .sources
+            .get(path)
+            .filter(|source| source.hash == source_hash)
+            .filter(|source| match &source.output {
+                Some(url) => self.page_path(url).is_file(),
+                None => true,
+            })
+            .filter(|source| match &source.scheduled {
+                Some(date) => *date > self.today,
+                None => true,
+            });
+
+        let changed = source.is_none();
+        let source = source.cloned().unwrap_or(Source {
+            hash: source_hash,
+            output: None,
+            entry: None,
+            scheduled: None,
+            aliases: Vec::new(),
+        });
+        self.current.sources.insert(path.to_path_buf(), source);
+        Ok(changed)
+    }
+
+    pub fn set_entry(&mut self, path: &Path, entry: Option<Entry>) {
+        if let Some(source) = self.current.sources.get_mut(path) {
+            source.output = entry.as_ref().map(|entry| entry.url.clone());
+            source.entry = entry;
+        }
+    }
+
+    pub fn set_scheduled(&mut self, path: &Path, date: String) {
+        if let Some(source) = self.current.sources.get_mut(path) {
+            source.scheduled = Some(date);
+        }
+    }
+
+    /// Articles held back because their date is after the build date.
+    pub fn scheduled(&self) -> Vec<(String, PathBuf)> {
+        let mut scheduled: Vec<_> = self
+            .current
+            .sources
+            .iter()
+            .filter_map(|(path, source)| Some((source.scheduled.clone()?, path.clone())))
+            .collect();
+        scheduled.sort();
+        scheduled
+    }
+
+    pub fn set_output(&mut self, path: &Path, url: &str) {
+        if let Some(source) = self.current.sources.get_mut(path) {
+            source.output = Some(url.to_string());
+        }
+    }
+
+    /// The URL each source is written to.
+    pub fn outputs(&self) -> impl Iterator<Item = (String, &Path)> {
+        self.current
+            .sources
+            .iter()
+            .filter_map(|(path, source)| Some((source.output.clone()?, path.as_path())))
+    }
+
+    pub fn set_aliases(&mut self, path: &Path, aliases: Vec<String>) {
+        if let Some(source) = self.current.sources.get_mut(path) {
+            source.aliases = aliases;
+        }
+    }
+
+    /// Old URLs given in source metadata, along with their target URL and
+    /// the source declaring them.
+    pub fn aliases(&self) -> impl Iterator<Item = (&str, &str, &Path)> {
+        self.current.sources.iter().flat_map(|(path, source)| {
+            source.aliases.iter().filter_map(move |alias| {
+                Some((alias.as_str(), source.output.as_deref()?, path.as_path()))
+            })
+        })
+    }
+
+    /// Redirects from URLs generated by earlier versions, which only change
+    /// along with the listings.
+    pub fn set_legacy(&mut self, legacy: BTreeMap<String, String>) {
+        self.current.legacy = legacy;
+    }
+
+    pub fn legacy(&self) -> &BTreeMap<String, String> {
+        &self.current.legacy
+    }
+
+    /// Whether this build generates a page at `url`, from a source or from
+    /// the listings. Pages left by previous builds do not count, as they are
+    /// removed once nothing generates them anymore.
+    pub fn is_content(&self, url: &str) -> bool {
+        let path = index_path(url);
+        self.touched.contains(&path)
+            || self.current.listed.contains(&path)
+            || self
+                .current
+                .sources
+                .values()
+                .filter_map(|source| source.output.as_deref())
+                .any(|output| index_path(output) == path)
+    }
+
+    /// Entries along with the source they were read from.
+    pub fn sourced_entries(&self) -> impl Iterator<Item = (&Path, &Entry)> + Clone {
+        self.current
+            .sources
+            .iter()
+            .filter_map(|(path, source)| Some((path.as_path(), source.entry.as_ref()?)))
+    }
+
+    pub fn entries(&self) -> Vec<&Entry> {
+        let mut entries: Vec<&Entry> = self
+            .current
+            .sources
+            .values()
+            .filter_map(|source| source.entry.as_ref())
+            .collect();
+        entries.sort_by(|a, b| (&a.date, &a.url).cmp(&(&b.date, &b.url)));
+        entries
+    }
+
+    /// Whether anything shown on listing pages (titles, dates, summaries,
+    /// taxonomies or the set of articles itself) differs from the last build,
+    /// or some previously generated file went missing. When they did, the
+    /// listing pages are expected to be written again.
+    pub fn listings_changed(&mut self) -> bool {
+        let listings: Vec<_> = self
+            .entries()
+            .into_iter()
+            .map(|entry| {
+                (
+                    &entry.title,
+                    &entry.url,
+                    &entry.date,
+                    &entry.tags,
+                    &entry.category,
+                    &entry.summary,
+                    entry.draft,
+                )
+            })
+            .collect();
+        self.current.listings = hash(serde_json::to_vec(&listings).unwrap());
+        let changed = self.current.listings != self.previous.listings
+            || self
+                .previous
+                .outputs
+                .keys()
+                .any(|path| !self.output_dir.join(path).is_file());
+        if changed {
+            self.current.listed.clear();
+        }
+        changed
+    }
+
+    fn page_path(&self, url: &str) -> PathBuf {
+        self.output_dir.join(index_path(url))
+    }
+
+    pub fn write(&mut self, path: &Path, contents: impl AsRef<[u8]>) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            path.components()
+                .all(|component| matches!(component, Component::Normal(_))),
+            "refusing to write {} outside the output directory",
+            path.display()
+        );
+        let contents_hash = hash(&contents);
+        let target = self.output_dir.join(path);
+        self.touched.insert(path.to_path_buf());
+        if let Some(files) = &mut self.files {
+            files.insert(path.to_path_buf(), contents.as_ref().to_vec());
+            self.written += 1;
+            return Ok(());
+        }
+        if target.is_file() && self.previous.outputs.get(path) == Some(&contents_hash) {
+            self.unchanged += 1;
+            return Ok(());
+        }
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&target, contents)?;
+        self.current
+            .outputs
+            .insert(path.to_path_buf(), contents_hash);
+        self.written += 1;
+        Ok(())
+    }
+
+    pub fn write_html(&mut self, url: &str, html: String) -> anyhow::Result<()> {
+        let path = index_path(url);
+        let html = match self.minify {
+            true => {
+                let minified = minify::html(&html);
+                self.saved += html.len().saturating_sub(minified.len());
+                minified
+            }
+            false => html,
+        };
+        self.write(&path, html)
+    }
+
+    pub fn write_page(&mut self, url: &str, page: Fragment) -> anyhow::Result<()> {
+        self.write_html(url, HtmlDocument(page).to_string())
+    }
+
+    /// Writes a page generated from the listings, which is kept by later
+    /// builds until the listings change.
+    pub fn write_listing(&mut self, url: &str, page: Fragment) -> anyhow::Result<()> {
+        self.current.listed.insert(index_path(url));
+        self.write_page(url, page)
+    }
+
+    /// Removes the files of previous builds that nothing generates anymore,
+    /// such as pages of deleted or unpublished sources.
+    pub fn prune(&mut self) -> anyhow::Result<()> {
+        if self.files.is_some() {
+            return Ok(());
+        }
+
+        let kept: HashSet<PathBuf> = self
+            .current
+            .sources
+            .values()
+            .filter_map(|source| source.output.as_deref())
+            .map(index_path)
+            .chain(self.current.listed.iter().cloned())
+            .chain(self.touched.iter().cloned())
+            .collect();
+        let stale: BTreeSet<PathBuf> = self
+            .generated
+            .iter()
+            .chain(self.current.outputs.keys())
+            .filter(|path| !kept.contains(*path))
+            .cloned()
+            .collect();
+
+        for path in stale {
+            self.current.outputs.remove(&path);
+            let target = self.output_dir.join(&path);
+            if !target.is_file() {
+                continue;
+            }
+            fs::remove_file(&target)?;
+            self.removed += 1;
+
+            // Leave no empty directories behind, up to the output directory.
+            for dir in target.ancestors().skip(1) {
+                if dir == self.output_dir || fs::remove_dir(dir).is_err() {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Every file of the output, relative to the output directory, whether
+    /// generated during this build or left from a previous one.
+    pub fn files(&self) -> Vec<&Path> {
+        match &self.files {
+            Some(files) => files.keys().map(PathBuf::as_path).collect(),
+            None => self
+                .current
+                .outputs
+                .keys()
+                .map(PathBuf::as_path)
+                .filter(|path| self.exists(path))
+                .collect(),
+        }
+    }
+
+    /// Whether `path` is part of the output, whether it was generated during
+    /// this build or is left from a previous one.
+    pub fn exists(&self, path: &Path) -> bool {
+        match &self.files {
+            Some(files) => files.contains_key(path),
+            None => self.output_dir.join(path).is_file(),
+        }
+    }
+
+    pub fn read(&self, path: &Path) -> anyhow::Result<Vec<u8>> {
+        match self.files.as_ref().and_then(|files| files.get(path)) {
+            Some(contents) => Ok(contents.clone()),
+            None => Ok(fs::read(self.output_dir.join(path))?),
+        }
+    }
+
+    pub fn save(self) -> anyhow::Result<()> {
+        if self.files.is_some() {
+            return Ok(());
+        }
+        fs::write(
+            self.output_dir.join(FILE_NAME),
+            serde_json::to_vec(&self.current)?,
+        )?;
+        Ok(())
+    }
+}
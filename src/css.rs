@@ -0,0 +1,378 @@
+use anyhow::{anyhow, bail, Context};
+
+/// Calls `f` on every position of `css` outside comments and strings, until
+/// it returns true, and returns that position.
+fn find(css: &str, mut f: impl FnMut(usize, &str) -> bool) -> Option<usize> {
+    let bytes = css.as_bytes();
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'/' if bytes.get(index + 1) == Some(&b'*') => {
+                index = css[index + 2..]
+                    .find("*/")
+                    .map_or(bytes.len(), |end| index + 2 + end + 2);
+            }
+            quote @ (b'"' | b'\'') => index = string_end(bytes, index, quote),
+            b'\\' => index += 1 + char_len(&css[index + 1..]),
+            _ if f(index, &css[index..]) => return Some(index),
+            _ => index += char_len(&css[index..]),
+        }
+    }
+    None
+}
+
+fn char_len(text: &str) -> usize {
+    text.chars().next().map_or(0, char::len_utf8)
+}
+
+/// The index right after the string starting at `start`.
+fn string_end(bytes: &[u8], start: usize, quote: u8) -> usize {
+    let mut index = start + 1;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' => index += 2,
+            byte if byte == quote => return index + 1,
+            _ => index += 1,
+        }
+    }
+    bytes.len()
+}
+
+fn starts_with_ignore_case(text: &str, prefix: &str) -> bool {
+    matches!(text.get(..prefix.len()), Some(start) if start.eq_ignore_ascii_case(prefix))
+}
+
+fn unquote(text: &str) -> &str {
+    let text = text.trim();
+    match text.as_bytes().first() {
+        Some(b'"' | b'\'') if text.len() >= 2 => &text[1..text.len() - 1],
+        _ => text,
+    }
+}
+
+fn is_external(url: &str) -> bool {
+    url.starts_with("//") || url.starts_with('#') || url.starts_with("data:") || url.contains("://")
+}
+
+/// Resolves `target` against the URL of the stylesheet referencing it.
+fn resolve(base: &str, target: &str) -> String {
+    if target.starts_with('/') {
+        return target.to_string();
+    }
+
+    let mut segments: Vec<&str> = base.split('/').collect();
+    segments.pop();
+    for segment in target.split('/') {
+        match segment {
+            "." => {}
+            ".." if segments.len() > 1 => {
+                segments.pop();
+            }
+            ".." => {}
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+/// Makes relative `url()` references absolute, as the bundle does not live
+/// next to the stylesheets it is made of.
+fn rebase(css: &str, base: &str) -> String {
+    let mut out = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = find(rest, |_, text| starts_with_ignore_case(text, "url(")) {
+        let start = start + "url(".len();
+        let end = match find(&rest[start..], |_, text| text.starts_with(')')) {
+            Some(end) => start + end,
+            None => break,
+        };
+        let target = unquote(&rest[start..end]);
+
+        out.push_str(&rest[..start]);
+        match is_external(target) {
+            true => out.push_str(&rest[start..end]),
+            false => out.push_str(&format!("\"{}\"", resolve(base, target))),
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Splits an `@import` prelude into its target and media query list.
+fn import(rule: &str) -> anyhow::Result<(&str, &str)> {
+    let end = if starts_with_ignore_case(rule, "url(") {
+        rule.find(')').map(|end| end + 1)
+    } else {
+        match rule.as_bytes().first() {
+            Some(&quote @ (b'"' | b'\'')) => Some(string_end(rule.as_bytes(), 0, quote)),
+            _ => None,
+        }
+    };
+    let end = end.ok_or_else(|| anyhow!("invalid `@import {}`", rule))?;
+
+    let target = &rule[..end];
+    let target = match starts_with_ignore_case(target, "url(") {
+        true => unquote(&target[4..target.len() - 1]),
+        false => unquote(target),
+    };
+    Ok((target, rule[end..].trim()))
+}
+
+/// Concatenates local stylesheets, inlining their `@import`s, looking up
+/// their contents through `read`.
+///
+/// External `@import`s cannot be inlined, and are only valid before any
+/// other rule, so stylesheets with some are not meant to be bundled, see
+/// [`Bundler::imports_external`].
+pub struct Bundler<'a> {
+    read: &'a dyn Fn(&str) -> Option<&'a [u8]>,
+    external: bool,
+    stack: Vec<String>,
+    out: String,
+}
+
+impl<'a> Bundler<'a> {
+    pub fn new(read: &'a dyn Fn(&str) -> Option<&'a [u8]>) -> Self {
+        Bundler {
+            read,
+            external: false,
+            stack: Vec::new(),
+            out: String::new(),
+        }
+    }
+
+    pub fn add(&mut self, url: &str) -> anyhow::Result<()> {
+        if self.stack.iter().any(|parent| parent == url) {
+            bail!("{} imports itself through {}", url, self.stack.join(" -> "));
+        }
+        let css = (self.read)(url)
+            .ok_or_else(|| anyhow!("stylesheet {} is not in the assets directory", url))?;
+        let css = std::str::from_utf8(css).with_context(|| format!("{} is not UTF-8", url))?;
+
+        self.stack.push(url.to_string());
+        let mut rest = css;
+        while let Some(start) = find(rest, |_, text| {
+            starts_with_ignore_case(text, "@import") || starts_with_ignore_case(text, "@charset")
+        }) {
+            self.out.push_str(&rebase(&rest[..start], url));
+            rest = &rest[start..];
+            let end = find(rest, |_, text| text.starts_with(';')).map_or(rest.len(), |end| end + 1);
+            let (statement, next) = rest.split_at(end);
+            rest = next;
+
+            // Only one `@charset` is allowed, at the very start, and bundles
+            // are always written as UTF-8 anyway.
+            if starts_with_ignore_case(statement, "@charset") {
+                continue;
+            }
+
+            let rule = statement["@import".len()..].trim_end_matches(';').trim();
+            let (target, media) = import(rule).with_context(|| format!("in {}", url))?;
+            if is_external(target) {
+                self.external = true;
+                self.out.push_str(statement);
+            } else if media.is_empty() {
+                self.add(&resolve(url, target))?;
+            } else {
+                self.out.push_str(&format!("@media {}{{", media));
+                self.add(&resolve(url, target))?;
+                self.out.push('}');
+            }
+        }
+        self.out.push_str(&rebase(rest, url));
+        self.out.push('\n');
+        self.stack.pop();
+        Ok(())
+    }
+
+    /// Whether some of the stylesheets added so far import external ones.
+    pub fn imports_external(&self) -> bool {
+        self.external
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+/// Drops comments and whitespace that does not change the meaning of `css`.
+pub fn minify(css: &str) -> String {
+    const SEPARATORS: &[u8] = b"{};,>";
+
+    let bytes = css.as_bytes();
+    let mut out: Vec<u8> = Vec::with_capacity(css.len());
+    let mut space = false;
+    let mut index = 0;
+    while index < bytes.len() {
+        let byte = bytes[index];
+        if byte.is_ascii_whitespace() {
+            space = true;
+            index += 1;
+            continue;
+        }
+        if byte == b'/' && bytes.get(index + 1) == Some(&b'*') {
+            space = true;
+            index = css[index + 2..]
+                .find("*/")
+                .map_or(bytes.len(), |end| index + 2 + end + 2);
+            continue;
+        }
+
+        if space
+            && !out.is_empty()
+            && !SEPARATORS.contains(out.last().unwrap())
+            && !SEPARATORS.contains(&byte)
+        {
+            out.push(b' ');
+        }
+        space = false;
+
+        let end = match byte {
+            b'"' | b'\'' => string_end(bytes, index, byte),
+            b'\\' => (index + 2).min(bytes.len()),
+            b'}' if out.last() == Some(&b';') => {
+                out.pop();
+                index + 1
+            }
+            _ => index + 1,
+        };
+        out.extend_from_slice(&bytes[index..end]);
+        index = end;
+    }
+
+    // Only whole characters and strings were copied, so this is still UTF-8
+    String::from_utf8(out).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn bundle(files: &[(&str, &str)], stylesheets: &[&str]) -> anyhow::Result<String> {
+        let files: HashMap<&str, &[u8]> = files
+            .iter()
+            .map(|(url, css)| (*url, css.as_bytes()))
+            .collect();
+        let read = |url: &str| files.get(url).copied();
+        let mut bundler = Bundler::new(&read);
+        for stylesheet in stylesheets {
+            bundler.add(stylesheet)?;
+        }
+        Ok(minify(&bundler.finish()))
+    }
+
+    #[test]
+    fn minify_drops_comments_and_whitespace() {
+        let css = "/* header */\nbody ,  p {\n  color : red ;\n  margin: 0 auto;\n}\n\na > b { }\n";
+        assert_eq!(minify(css), "body,p{color : red;margin: 0 auto}a>b{}");
+    }
+
+    #[test]
+    fn minify_keeps_strings() {
+        let css = "a::before { content: \"  /* not a comment */  \"; }";
+        assert_eq!(
+            minify(css),
+            "a::before{content: \"  /* not a comment */  \"}"
+        );
+    }
+
+    #[test]
+    fn minify_keeps_descendant_combinators() {
+        assert_eq!(minify("nav   a\n.b {x:y}"), "nav a .b{x:y}");
+    }
+
+    #[test]
+    fn rebase_resolves_relative_urls() {
+        let css = "a { background: url(img/a.png) } b { background: url('../b.png') }";
+        assert_eq!(
+            rebase(css, "/assets/css/style.css"),
+            "a { background: url(\"/assets/css/img/a.png\") } b { background: url(\"/assets/b.png\") }"
+        );
+    }
+
+    #[test]
+    fn rebase_keeps_absolute_and_external_urls() {
+        let css =
+            "a { x: url(/a.png); y: url(https://cdn/b.png); z: url(data:image/png;base64,AA==) }";
+        assert_eq!(
+            rebase(css, "/assets/css/style.css"),
+            "a { x: url(\"/a.png\"); y: url(https://cdn/b.png); z: url(data:image/png;base64,AA==) }"
+        );
+    }
+
+    #[test]
+    fn inlines_imports_relative_to_the_importer() {
+        let files = [
+            (
+                "/assets/style.css",
+                "@import \"css/base.css\";\nbody { x: 1 }",
+            ),
+            (
+                "/assets/css/base.css",
+                "@import url(reset.css);\np { background: url(p.png) }",
+            ),
+            ("/assets/css/reset.css", "* { margin: 0 }"),
+        ];
+        assert_eq!(
+            bundle(&files, &["/assets/style.css"]).unwrap(),
+            "*{margin: 0}p{background: url(\"/assets/css/p.png\")}body{x: 1}"
+        );
+    }
+
+    #[test]
+    fn wraps_imports_with_media_queries() {
+        let files = [
+            ("/a.css", "@import \"print.css\" print;\na { x: 1 }"),
+            ("/print.css", "nav { display: none }"),
+        ];
+        assert_eq!(
+            bundle(&files, &["/a.css"]).unwrap(),
+            "@media print{nav{display: none}}a{x: 1}"
+        );
+    }
+
+    #[test]
+    fn drops_charset() {
+        let files = [
+            ("/a.css", "@charset \"utf-8\";\na { x: 1 }"),
+            ("/b.css", "b { y: 2 }"),
+        ];
+        assert_eq!(
+            bundle(&files, &["/a.css", "/b.css"]).unwrap(),
+            "a{x: 1}b{y: 2}"
+        );
+    }
+
+    #[test]
+    fn detects_nested_external_imports() {
+        let files = [
+            ("/a.css", "@import \"b.css\" print;\na { x: 1 }"),
+            ("/b.css", "@import url(https://cdn/font.css);\nb { y: 2 }"),
+            ("/c.css", "c { z: 3 }"),
+        ];
+        let read = |url: &str| {
+            files
+                .iter()
+                .find(|(name, _)| *name == url)
+                .map(|(_, css)| css.as_bytes())
+        };
+
+        let mut bundler = Bundler::new(&read);
+        bundler.add("/c.css").unwrap();
+        assert!(!bundler.imports_external());
+        bundler.add("/a.css").unwrap();
+        assert!(bundler.imports_external());
+    }
+
+    #[test]
+    fn rejects_import_cycles_and_missing_files() {
+        let files = [
+            ("/a.css", "@import \"b.css\";"),
+            ("/b.css", "@import \"a.css\";"),
+        ];
+        assert!(bundle(&files, &["/a.css"]).is_err());
+        assert!(bundle(&files, &["/missing.css"]).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// A problem with a source file, reported to its author.
+pub struct Diagnostic {
+    pub path: PathBuf,
+    pub field: Option<&'static str>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(path: &Path, message: impl Into<String>) -> Self {
+        Diagnostic {
+            path: path.to_path_buf(),
+            field: None,
+            message: message.into(),
+        }
+    }
+
+    pub fn field(path: &Path, field: &'static str, message: impl Into<String>) -> Self {
+        Diagnostic {
+            field: Some(field),
+            ..Diagnostic::new(path, message)
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.field {
+            Some(field) => write!(f, "{}: `{}`: {}", self.path.display(), field, self.message),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+/// Every problem found in the sources, so that they can all be fixed at
+/// once instead of one build at a time.
+#[derive(Default)]
+pub struct Diagnostics(Vec<Diagnostic>);
+
+impl Diagnostics {
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.0.push(diagnostic);
+    }
+
+    /// Fails with every diagnostic collected so far, if any.
+    pub fn check(&mut self) -> anyhow::Result<()> {
+        match self.0.is_empty() {
+            true => Ok(()),
+            false => Err(std::mem::take(self).into()),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "found {} error(s) in sources", self.0.len())?;
+        for diagnostic in &self.0 {
+            write!(f, "\n  {}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for Diagnostics {}
@@ -0,0 +1,374 @@
+use crate::{Article, Author, BlogData, DATE_FORMAT};
+use anyhow::Context;
+use pastex::output::html;
+use std::fmt::Write;
+use time::{
+    format_description::well_known::{Rfc2822, Rfc3339},
+    Date, OffsetDateTime, Time,
+};
+
+#[derive(serde::Deserialize)]
+#[serde(default)]
+pub struct Feeds {
+    atom: bool,
+    rss: bool,
+    json: bool,
+}
+
+impl Default for Feeds {
+    fn default() -> Self {
+        Feeds {
+            atom: true,
+            rss: true,
+            json: true,
+        }
+    }
+}
+
+impl Feeds {
+    pub fn enabled(&self) -> impl Iterator<Item = Format> + '_ {
+        Format::ALL.into_iter().filter(|format| match format {
+            Format::Atom => self.atom,
+            Format::Rss => self.rss,
+            Format::Json => self.json,
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Atom,
+    Rss,
+    Json,
+}
+
+impl Format {
+    const ALL: [Format; 3] = [Format::Atom, Format::Rss, Format::Json];
+
+    pub fn path(self) -> &'static str {
+        match self {
+            Format::Atom => "/atom.xml",
+            Format::Rss => "/rss.xml",
+            Format::Json => "/feed.json",
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Format::Atom => "application/atom+xml",
+            Format::Rss => "application/rss+xml",
+            Format::Json => "application/feed+json",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Atom => "Atom",
+            Format::Rss => "RSS",
+            Format::Json => "JSON Feed",
+        }
+    }
+
+    pub fn render(self, blog_data: &BlogData, entries: &[&Entry]) -> anyhow::Result<String> {
+        let feed = match self {
+            Format::Atom => atom(blog_data, entries),
+            Format::Rss => rss(blog_data, entries),
+            Format::Json => json(blog_data, entries),
+        };
+        feed.with_context(|| format!("cannot write {}", self.path()))
+    }
+}
+
+/// Everything the feeds need from an article, kept as rendered strings so
+/// that it can be stored in the build cache instead of re-parsing sources.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct Entry {
+    pub title: String,
+    pub url: String,
+    pub date: String,
+    pub tags: Vec<String>,
+    pub category: Option<String>,
+    pub summary: Option<String>,
+    pub contents: String,
+    pub draft: bool,
+    #[serde(default)]
+    pub lang: Option<String>,
+}
+
+impl Entry {
+    pub fn new(article: &Article) -> Self {
+        let (contents, summary) = html::output(&article.document);
+
+        Entry {
+            title: article.title.clone(),
+            url: article.url.clone(),
+            date: article.date.format(&DATE_FORMAT).unwrap(),
+            tags: article.tags.clone(),
+            category: article.category.clone(),
+            summary: summary.map(|summary| summary.to_string()),
+            contents: contents.to_string(),
+            draft: article.draft,
+            lang: article.lang.clone(),
+        }
+    }
+
+    pub fn date(&self) -> anyhow::Result<Date> {
+        Ok(Date::parse(&self.date, &DATE_FORMAT)?)
+    }
+
+    fn categories(&self) -> impl Iterator<Item = &String> {
+        self.category.iter().chain(&self.tags)
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn timestamp(date: Date) -> anyhow::Result<String> {
+    Ok(date
+        .with_time(Time::MIDNIGHT)
+        .assume_utc()
+        .format(&Rfc3339)?)
+}
+
+fn rfc2822(date: Date) -> anyhow::Result<String> {
+    Ok(date
+        .with_time(Time::MIDNIGHT)
+        .assume_utc()
+        .format(&Rfc2822)?)
+}
+
+fn tagline(blog_data: &BlogData) -> String {
+    html::output_fragment(&pastex::document::process_fragment(&blog_data.tagline)).to_string()
+}
+
+/// The author of the site, which some feed formats require.
+fn author(blog_data: &BlogData) -> anyhow::Result<&Author> {
+    blog_data
+        .author
+        .as_ref()
+        .context("[author] is not set, add it or disable the feed under [feeds]")
+}
+
+fn atom(blog_data: &BlogData, entries: &[&Entry]) -> anyhow::Result<String> {
+    let author = author(blog_data)?;
+    let feed_url = blog_data.absolute_url(Format::Atom.path())?;
+    let home_url = blog_data.absolute_url("/")?;
+    let updated = match entries.last() {
+        Some(entry) => timestamp(entry.date()?)?,
+        None => timestamp(OffsetDateTime::UNIX_EPOCH.date())?,
+    };
+
+    let mut out = String::new();
+    writeln!(out, r#"<?xml version="1.0" encoding="utf-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<feed xmlns="http://www.w3.org/2005/Atom" xml:base="{}" xml:lang="{}">"#,
+        escape(&home_url),
+        escape(&blog_data.lang)
+    )?;
+    writeln!(out, "  <id>{}</id>", escape(&feed_url))?;
+    writeln!(out, "  <title>{}</title>", escape(&blog_data.title))?;
+    writeln!(
+        out,
+        r#"  <subtitle type="html">{}</subtitle>"#,
+        escape(&tagline(blog_data))
+    )?;
+    writeln!(out, "  <updated>{}</updated>", updated)?;
+    writeln!(out, r#"  <link rel="self" href="{}"/>"#, escape(&feed_url))?;
+    writeln!(
+        out,
+        r#"  <link rel="alternate" href="{}"/>"#,
+        escape(&home_url)
+    )?;
+    writeln!(out, "  <author>")?;
+    writeln!(out, "    <name>{}</name>", escape(&author.name))?;
+    if let Some(email) = &author.email {
+        writeln!(out, "    <email>{}</email>", escape(email))?;
+    }
+    if let Some(uri) = &author.uri {
+        writeln!(out, "    <uri>{}</uri>", escape(uri))?;
+    }
+    writeln!(out, "  </author>")?;
+
+    for entry in entries.iter().rev() {
+        let url = blog_data.absolute_url(&entry.url)?;
+        let date = timestamp(entry.date()?)?;
+
+        match &entry.lang {
+            Some(lang) => writeln!(out, r#"  <entry xml:lang="{}">"#, escape(lang))?,
+            None => writeln!(out, "  <entry>")?,
+        }
+        writeln!(out, "    <id>{}</id>", escape(&url))?;
+        writeln!(out, "    <title>{}</title>", escape(&entry.title))?;
+        writeln!(
+            out,
+            r#"    <link rel="alternate" href="{}"/>"#,
+            escape(&url)
+        )?;
+        writeln!(out, "    <published>{}</published>", date)?;
+        writeln!(out, "    <updated>{}</updated>", date)?;
+        for category in entry.categories() {
+            writeln!(out, r#"    <category term="{}"/>"#, escape(category))?;
+        }
+        if let Some(summary) = &entry.summary {
+            writeln!(
+                out,
+                r#"    <summary type="html">{}</summary>"#,
+                escape(summary)
+            )?;
+        }
+        writeln!(
+            out,
+            r#"    <content type="html">{}</content>"#,
+            escape(&entry.contents)
+        )?;
+        writeln!(out, "  </entry>")?;
+    }
+
+    writeln!(out, "</feed>")?;
+    Ok(out)
+}
+
+fn rss(blog_data: &BlogData, entries: &[&Entry]) -> anyhow::Result<String> {
+    let feed_url = blog_data.absolute_url(Format::Rss.path())?;
+    let home_url = blog_data.absolute_url("/")?;
+
+    let mut out = String::new();
+    writeln!(out, r#"<?xml version="1.0" encoding="utf-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom">"#
+    )?;
+    writeln!(out, "  <channel>")?;
+    writeln!(out, "    <title>{}</title>", escape(&blog_data.title))?;
+    writeln!(out, "    <link>{}</link>", escape(&home_url))?;
+    writeln!(
+        out,
+        "    <description>{}</description>",
+        escape(&tagline(blog_data))
+    )?;
+    writeln!(out, "    <language>{}</language>", escape(&blog_data.lang))?;
+    writeln!(
+        out,
+        r#"    <atom:link rel="self" type="{}" href="{}"/>"#,
+        Format::Rss.mime_type(),
+        escape(&feed_url)
+    )?;
+    if let Some(entry) = entries.last() {
+        writeln!(
+            out,
+            "    <lastBuildDate>{}</lastBuildDate>",
+            rfc2822(entry.date()?)?
+        )?;
+    }
+
+    for entry in entries.iter().rev() {
+        let url = blog_data.absolute_url(&entry.url)?;
+        let description = entry.summary.as_ref().unwrap_or(&entry.contents);
+
+        writeln!(out, "    <item>")?;
+        writeln!(out, "      <title>{}</title>", escape(&entry.title))?;
+        writeln!(out, "      <link>{}</link>", escape(&url))?;
+        writeln!(
+            out,
+            r#"      <guid isPermaLink="true">{}</guid>"#,
+            escape(&url)
+        )?;
+        writeln!(out, "      <pubDate>{}</pubDate>", rfc2822(entry.date()?)?)?;
+        for category in entry.categories() {
+            writeln!(out, "      <category>{}</category>", escape(category))?;
+        }
+        writeln!(
+            out,
+            "      <description>{}</description>",
+            escape(description)
+        )?;
+        writeln!(out, "    </item>")?;
+    }
+
+    writeln!(out, "  </channel>")?;
+    writeln!(out, "</rss>")?;
+    Ok(out)
+}
+
+#[derive(serde::Serialize)]
+struct JsonFeed<'a> {
+    version: &'static str,
+    title: &'a str,
+    home_page_url: String,
+    feed_url: String,
+    description: String,
+    language: &'a str,
+    authors: [JsonAuthor<'a>; 1],
+    items: Vec<JsonItem<'a>>,
+}
+
+#[derive(serde::Serialize)]
+struct JsonAuthor<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<&'a str>,
+}
+
+#[derive(serde::Serialize)]
+struct JsonItem<'a> {
+    id: String,
+    url: String,
+    title: &'a str,
+    content_html: &'a str,
+    date_published: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<&'a str>,
+}
+
+fn json(blog_data: &BlogData, entries: &[&Entry]) -> anyhow::Result<String> {
+    let author = author(blog_data)?;
+    let items = entries
+        .iter()
+        .rev()
+        .map(|entry| {
+            let url = blog_data.absolute_url(&entry.url)?;
+
+            Ok(JsonItem {
+                id: url.clone(),
+                url,
+                title: &entry.title,
+                content_html: &entry.contents,
+                date_published: timestamp(entry.date()?)?,
+                tags: entry.categories().map(String::as_str).collect(),
+                language: entry.lang.as_deref(),
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let feed = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title: &blog_data.title,
+        home_page_url: blog_data.absolute_url("/")?,
+        feed_url: blog_data.absolute_url(Format::Json.path())?,
+        description: tagline(blog_data),
+        language: &blog_data.lang,
+        authors: [JsonAuthor {
+            name: &author.name,
+            url: author.uri.as_deref(),
+        }],
+        items,
+    };
+
+    Ok(serde_json::to_string_pretty(&feed)?)
+}
@@ -0,0 +1,53 @@
+use time::Month;
+
+pub fn default() -> String {
+    "en".to_string()
+}
+
+/// Whether `lang` looks like a language tag, such as `en` or `fr-CA`.
+pub fn is_valid(lang: &str) -> bool {
+    let mut subtags = lang.split('-');
+    let primary = subtags.next().unwrap_or_default();
+    (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+}
+
+/// Name of a month in the given language, falling back to English for
+/// languages without a translation.
+pub fn month(month: Month, lang: &str) -> &'static str {
+    let primary = lang.split('-').next().unwrap_or_default();
+    let names = match primary.to_ascii_lowercase().as_str() {
+        "fr" => [
+            "janvier",
+            "février",
+            "mars",
+            "avril",
+            "mai",
+            "juin",
+            "juillet",
+            "août",
+            "septembre",
+            "octobre",
+            "novembre",
+            "décembre",
+        ],
+        _ => [
+            "January",
+            "February",
+            "March",
+            "April",
+            "May",
+            "June",
+            "July",
+            "August",
+            "September",
+            "October",
+            "November",
+            "December",
+        ],
+    };
+    names[month as usize - 1]
+}
@@ -0,0 +1,769 @@
+//! Generates a static blog from pastex articles and pages.
+//!
+//! The `blog-generator` binary runs it with the default theme. A site with a
+//! look of its own depends on this crate instead, implements
+//! [`theme::Theme`] and passes it to [`run`] from its own `main`.
+
+use clap::{Args, Parser, Subcommand};
+use diagnostic::{Diagnostic, Diagnostics};
+use dolmen::Fragment;
+use once_cell::sync::Lazy;
+use pastex::document::Document;
+use rayon::prelude::*;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::OsStr,
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use theme::Theme;
+use time::{format_description::FormatItem, Date, OffsetDateTime};
+
+pub mod archive;
+pub mod assets;
+mod cache;
+mod css;
+mod diagnostic;
+pub mod feed;
+pub mod lang;
+mod links;
+mod markup;
+pub mod menu;
+mod minify;
+pub mod pagination;
+pub mod permalink;
+mod redirect;
+mod serve;
+pub mod taxonomy;
+pub mod theme;
+mod watch;
+
+pub static DATE_FORMAT: Lazy<Vec<FormatItem<'_>>> =
+    Lazy::new(|| time::format_description::parse("[year]-[month]-[day]").unwrap());
+
+#[derive(serde::Deserialize)]
+pub struct Social {
+    pub name: String,
+    pub icon_name: String,
+    pub url: String,
+}
+
+/// The site configuration, read from `blog.toml`.
+#[derive(serde::Deserialize)]
+pub struct BlogData {
+    pub title: String,
+    pub tagline: String,
+    /// Where the site is published, needed for the absolute URLs of feeds.
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// Language of the site, overridden by the `lang` metadata of sources.
+    #[serde(default = "lang::default")]
+    pub lang: String,
+    /// Needed by Atom and JSON feeds.
+    #[serde(default)]
+    pub author: Option<Author>,
+    pub footer: String,
+    pub socials: Vec<Social>,
+    #[serde(default)]
+    pub stylesheets: Vec<String>,
+    #[serde(default)]
+    pub menu: menu::Menu,
+    #[serde(default)]
+    assets: assets::Assets,
+    #[serde(skip)]
+    asset_urls: BTreeMap<String, String>,
+    #[serde(default)]
+    pub feeds: feed::Feeds,
+    #[serde(default)]
+    pub pagination: pagination::Pagination,
+    #[serde(default)]
+    pub permalinks: permalink::Permalinks,
+    #[serde(default)]
+    redirects: BTreeMap<String, String>,
+    #[serde(default)]
+    redirect_files: Vec<redirect::RuleFile>,
+    #[serde(default)]
+    minify_html: bool,
+}
+
+#[derive(serde::Deserialize)]
+pub struct Author {
+    pub name: String,
+    pub email: Option<String>,
+    pub uri: Option<String>,
+}
+
+/// An article, as given to the theme.
+pub struct Article {
+    pub document: Document,
+    pub path: PathBuf,
+    pub title: String,
+    pub date: Date,
+    pub url: String,
+    pub legacy_url: Option<String>,
+    pub aliases: Vec<String>,
+    pub tags: Vec<String>,
+    pub category: Option<String>,
+    pub lang: Option<String>,
+    pub draft: bool,
+}
+
+fn metadata_field<'a>(document: &'a Document, name: &str) -> Option<&'a str> {
+    document.metadata.extra.get(name).map(String::as_str)
+}
+
+fn metadata_list<'a>(document: &'a Document, name: &str) -> Vec<&'a str> {
+    metadata_field(document, name)
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl BlogData {
+    pub fn absolute_url(&self, path: &str) -> anyhow::Result<String> {
+        match &self.base_url {
+            Some(base_url) => Ok(format!("{}{}", base_url.trim_end_matches('/'), path)),
+            None => anyhow::bail!(
+                "base_url is not set, it is needed for the absolute URL of {}",
+                path
+            ),
+        }
+    }
+
+    /// The URL to link an asset with, fingerprinted if enabled.
+    pub fn asset_url(&self, url: &str) -> String {
+        self.asset_urls
+            .get(url)
+            .cloned()
+            .unwrap_or_else(|| url.to_string())
+    }
+}
+
+fn slug<'a>(document: &'a Document, path: &'a Path) -> Result<&'a str, Diagnostic> {
+    match metadata_field(document, "slug") {
+        Some(slug) => Ok(slug),
+        None => path.file_stem().and_then(OsStr::to_str).ok_or_else(|| {
+            Diagnostic::field(
+                path,
+                "slug",
+                "missing, and the file name is not valid UTF-8",
+            )
+        }),
+    }
+}
+
+fn aliases(document: &Document, path: &Path) -> Result<Vec<String>, Diagnostic> {
+    metadata_list(document, "aliases")
+        .into_iter()
+        .map(|alias| match redirect::is_valid(alias) {
+            true => Ok(redirect::normalize(alias)),
+            false => Err(Diagnostic::field(
+                path,
+                "aliases",
+                format!(
+                    "`{}` is not a valid alias, it must not contain `.` or `..` segments",
+                    alias
+                ),
+            )),
+        })
+        .collect()
+}
+
+/// The language of a source, when it differs from the site's.
+fn lang(document: &Document, path: &Path) -> Result<Option<String>, Diagnostic> {
+    match metadata_field(document, "lang") {
+        Some(lang) if !lang::is_valid(lang) => Err(Diagnostic::field(
+            path,
+            "lang",
+            format!(
+                "`{}` is not a valid language tag, expected something like `en` or `fr-CA`",
+                lang
+            ),
+        )),
+        lang => Ok(lang.map(str::to_string)),
+    }
+}
+
+/// Parses a source and checks the metadata shared by articles and pages.
+fn load(path: &Path) -> Result<(Document, String), Diagnostic> {
+    let document = pastex::document::process(path)
+        .map_err(|error| Diagnostic::new(path, format!("{:#}", error)))?;
+
+    let title = match &document.metadata.title {
+        Some(title) if !title.trim().is_empty() => title.clone(),
+        _ => {
+            return Err(Diagnostic::field(
+                path,
+                "title",
+                "missing, every source needs one",
+            ))
+        }
+    };
+    let slug = slug(&document, path)?;
+    if !permalink::is_segment(slug) {
+        return Err(Diagnostic::field(
+            path,
+            "slug",
+            format!(
+                "`{}` is not a valid slug, expected a non-empty name without slashes, other than `.` and `..`",
+                slug
+            ),
+        ));
+    }
+    Ok((document, title))
+}
+
+fn article(
+    path: &Path,
+    site: &SiteArgs,
+    permalink: &permalink::Template,
+) -> Result<Option<Article>, Diagnostic> {
+    let (document, title) = load(path)?;
+    if !site.drafts && (document.metadata.draft || document.metadata.date.is_none()) {
+        return Ok(None);
+    }
+
+    let date = match &document.metadata.date {
+        Some(date) => Date::parse(date, &DATE_FORMAT).map_err(|_| {
+            Diagnostic::field(
+                path,
+                "date",
+                format!("`{}` is not a valid date, expected YYYY-MM-DD", date),
+            )
+        })?,
+        None => site.today(),
+    };
+    let slug = slug(&document, path)?;
+    let lang = lang(&document, path)?;
+    let mut tags = metadata_list(&document, "tags");
+    let mut seen = HashSet::new();
+    tags.retain(|tag| seen.insert(*tag));
+    taxonomy::check_names(path, "tags", &tags)?;
+    let category = metadata_field(&document, "category");
+    taxonomy::check_names(path, "category", category.as_slice())?;
+
+    Ok(Some(Article {
+        url: permalink.render(Some(date), slug),
+        legacy_url: permalink.legacy(date, slug),
+        aliases: aliases(&document, path)?,
+        date,
+        draft: document.metadata.draft || document.metadata.date.is_none(),
+        tags: tags.into_iter().map(str::to_string).collect(),
+        category: category.map(str::to_string),
+        lang,
+        path: path.to_path_buf(),
+        title,
+        document,
+    }))
+}
+
+/// Loads the given article sources, returning the published articles and,
+/// separately, the ones scheduled after the build date.
+/// Sources with errors are skipped and reported in `diagnostics`.
+fn articles(
+    paths: &[PathBuf],
+    site: &SiteArgs,
+    permalink: &permalink::Template,
+    diagnostics: &mut Diagnostics,
+) -> (Vec<Article>, Vec<Article>) {
+    let results: Vec<_> = paths
+        .par_iter()
+        .map(|path| article(path, site, permalink))
+        .collect();
+
+    let mut articles = Vec::new();
+    for result in results {
+        match result {
+            Ok(article) => articles.extend(article),
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
+    }
+    let (mut articles, scheduled): (Vec<Article>, Vec<Article>) = articles
+        .into_iter()
+        .partition(|article| site.future || article.date <= site.today());
+
+    articles.sort_unstable_by(|a, b| (a.date, &a.url).cmp(&(b.date, &b.url)));
+    (articles, scheduled)
+}
+
+/// Details of a page for the head of the document.
+#[derive(Default)]
+pub struct PageMeta {
+    pub prev: Option<String>,
+    pub next: Option<String>,
+    pub noindex: bool,
+    pub lang: Option<String>,
+}
+
+fn layout(
+    blog_data: &BlogData,
+    theme: &dyn Theme,
+    url: &str,
+    meta: &PageMeta,
+    inner: Fragment,
+) -> Fragment {
+    let page = theme::Page {
+        site: blog_data,
+        url,
+        meta,
+        lang: meta.lang.as_deref().unwrap_or(&blog_data.lang),
+    };
+    theme.layout(&page, inner)
+}
+
+struct HtmlDocument(Fragment);
+
+impl fmt::Display for HtmlDocument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "<!DOCTYPE html>")?;
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Parser)]
+#[clap(version, about)]
+struct Cli {
+    #[clap(flatten)]
+    site: SiteArgs,
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Build the whole site into the output directory
+    Build,
+    /// Check the site for errors without writing anything
+    Check,
+    /// Build the site, then rebuild it whenever its sources change
+    Watch,
+    /// Serve the site locally, rebuilding and reloading on changes
+    Serve(serve::ServeArgs),
+}
+
+#[derive(Args, Clone)]
+struct SiteArgs {
+    /// Directory containing the site sources (articles, pages)
+    #[clap(long, short, global = true, default_value = ".")]
+    root: PathBuf,
+    /// Site configuration file [default: <ROOT>/blog.toml]
+    #[clap(long, short, global = true)]
+    config: Option<PathBuf>,
+    /// Directory the generated site is written to
+    #[clap(long, short, global = true, default_value = "output")]
+    output: PathBuf,
+    /// Maximum number of files processed in parallel [default: number of CPUs]
+    #[clap(long, short, global = true)]
+    jobs: Option<usize>,
+    /// Include drafts and undated articles, marked as such
+    #[clap(long, global = true)]
+    drafts: bool,
+    /// Include articles dated after the build date
+    #[clap(long, global = true)]
+    future: bool,
+    /// Build as if on this date (YYYY-MM-DD) instead of today
+    #[clap(long, global = true, parse(try_from_str = parse_date))]
+    date: Option<Date>,
+    /// Ignore the build cache and rebuild everything
+    #[clap(long, global = true)]
+    force: bool,
+}
+
+fn parse_date(date: &str) -> Result<Date, time::error::Parse> {
+    Date::parse(date, &DATE_FORMAT)
+}
+
+impl SiteArgs {
+    fn config_path(&self) -> PathBuf {
+        self.config
+            .clone()
+            .unwrap_or_else(|| self.root.join("blog.toml"))
+    }
+
+    fn today(&self) -> Date {
+        self.date
+            .unwrap_or_else(|| OffsetDateTime::now_utc().date())
+    }
+
+    /// Build settings that change the generated site, for the build cache.
+    fn options(&self) -> String {
+        let mut options = Vec::new();
+        if self.drafts {
+            options.push(format!("drafts:{}", self.today()));
+        }
+        if self.future {
+            options.push("future".to_string());
+        }
+        if let Some(date) = self.date {
+            options.push(format!("date:{}", date));
+        }
+        options.join(",")
+    }
+
+    fn sources(&self, dir: &str) -> anyhow::Result<Vec<PathBuf>> {
+        let pattern = format!("{}/**/*.px", self.root.join(dir).display());
+        Ok(glob::glob(&pattern)?.collect::<Result<Vec<_>, _>>()?)
+    }
+}
+
+struct Summary {
+    articles: usize,
+    pages: usize,
+    assets: usize,
+    written: usize,
+    unchanged: usize,
+    removed: usize,
+    saved: Option<usize>,
+    scheduled: Vec<(String, PathBuf)>,
+    broken: Vec<Diagnostic>,
+}
+
+impl Summary {
+    fn print(&self, elapsed: Duration) {
+        println!(
+            "built {} article(s) and {} page(s), copied {} asset(s), wrote {} file(s) ({} unchanged) in {:.2?}",
+            self.articles, self.pages, self.assets, self.written, self.unchanged, elapsed
+        );
+        if self.removed > 0 {
+            println!("  removed {} stale file(s)", self.removed);
+        }
+        if let Some(saved) = self.saved {
+            println!("  minified HTML, saved {} byte(s)", saved);
+        }
+        for (date, path) in &self.scheduled {
+            println!("  scheduled for {}: {}", date, path.display());
+        }
+        for diagnostic in &self.broken {
+            println!("  warning: {}", diagnostic);
+        }
+    }
+}
+
+fn page(
+    blog_data: &BlogData,
+    theme: &dyn Theme,
+    path: &Path,
+) -> Result<(String, Vec<String>, String), Diagnostic> {
+    let (document, title) = load(path)?;
+    let url = blog_data
+        .permalinks
+        .page
+        .render(None, slug(&document, path)?);
+    let aliases = aliases(&document, path)?;
+    let meta = PageMeta {
+        lang: lang(&document, path)?,
+        ..PageMeta::default()
+    };
+    let page = theme.page(title, theme::Body::new(&document));
+    let page = layout(blog_data, theme, &url, &meta, page);
+
+    Ok((url, aliases, HtmlDocument(page).to_string()))
+}
+
+/// Builds the site, or only checks it when `dry_run` is set, in which case
+/// nothing is written to the output directory.
+fn build(site: &SiteArgs, theme: &dyn Theme, dry_run: bool) -> anyhow::Result<Summary> {
+    let config = fs::read_to_string(site.config_path())?;
+    let mut blog_data: BlogData = toml::from_str(&config)?;
+
+    anyhow::ensure!(
+        blog_data.pagination.page_size > 0,
+        "pagination.page_size must be at least 1"
+    );
+    anyhow::ensure!(
+        lang::is_valid(&blog_data.lang),
+        "lang `{}` is not a valid language tag, expected something like `en` or `fr-CA`",
+        blog_data.lang
+    );
+    blog_data.menu.validate()?;
+    blog_data.permalinks.validate()?;
+
+    let mut assets = blog_data.assets.load(&site.root)?;
+    if blog_data.assets.bundle {
+        blog_data.stylesheets = blog_data
+            .assets
+            .bundle(&mut assets, &blog_data.stylesheets)?;
+    }
+    blog_data.asset_urls = assets::urls(&assets);
+    let mut cache = cache::Cache::load(site, &config, &blog_data.asset_urls, dry_run)?;
+    cache.minify = blog_data.minify_html;
+    assets::copy(&assets, &mut cache)?;
+
+    let sources = site.sources("articles")?;
+    let mut changed = Vec::new();
+    for path in &sources {
+        if cache.check(path)? {
+            changed.push(path.clone());
+        }
+    }
+
+    let permalink = &blog_data.permalinks.article;
+    let mut diagnostics = Diagnostics::default();
+    let (mut articles, scheduled) = articles(&changed, site, permalink, &mut diagnostics);
+    for article in scheduled {
+        cache.set_scheduled(&article.path, article.date.format(&DATE_FORMAT)?);
+    }
+    let mut entries: HashMap<PathBuf, feed::Entry> = articles
+        .par_iter()
+        .map(|article| (article.path.clone(), feed::Entry::new(article)))
+        .collect();
+    for path in &changed {
+        cache.set_entry(path, entries.remove(path));
+    }
+    for article in &articles {
+        cache.set_aliases(&article.path, article.aliases.clone());
+    }
+
+    let mut pages = Vec::new();
+    for path in site.sources("pages")? {
+        if cache.check(&path)? {
+            pages.push(path);
+        }
+    }
+    let rendered = pages
+        .par_iter()
+        .map(|path| page(&blog_data, theme, path))
+        .collect::<Vec<_>>();
+    let mut pages_html = Vec::new();
+    for (path, page) in pages.iter().zip(rendered) {
+        match page {
+            Ok((url, aliases, html)) => {
+                cache.set_output(path, &url);
+                cache.set_aliases(path, aliases);
+                pages_html.push((url, html));
+            }
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
+    }
+
+    // Every output URL and term is known at this point, including those of
+    // unchanged sources, so collisions are caught before anything is written.
+    // Listing pages are only written when the listings change, but where
+    // they go already follows from the entries.
+    let entries = cache.entries();
+    let dates = entries
+        .iter()
+        .map(|entry| entry.date())
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let mut generated: HashSet<String> =
+        pagination::urls(entries.len(), blog_data.pagination.page_size).collect();
+    generated.insert("/".to_string());
+    generated.extend(taxonomy::urls(&entries));
+    generated.extend(archive::urls(permalink, dates));
+    generated.extend(
+        blog_data
+            .feeds
+            .enabled()
+            .map(|format| format.path().to_string()),
+    );
+    permalink::check_unique(cache.outputs(), &generated, &mut diagnostics);
+    taxonomy::check_collisions(cache.sourced_entries(), &mut diagnostics);
+    diagnostics.check()?;
+
+    let published: Vec<&feed::Entry> = cache
+        .entries()
+        .into_iter()
+        .filter(|entry| !entry.draft)
+        .collect();
+    let feeds = blog_data
+        .feeds
+        .enabled()
+        .map(|format| Ok((format, format.render(&blog_data, &published)?)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    for (format, feed) in feeds {
+        cache.write(Path::new(&format.path()[1..]), feed)?;
+    }
+
+    let mut summary = Summary {
+        articles: 0,
+        pages: 0,
+        assets: 0,
+        written: 0,
+        unchanged: 0,
+        removed: 0,
+        saved: None,
+        scheduled: Vec::new(),
+        broken: Vec::new(),
+    };
+
+    if cache.listings_changed() {
+        let unchanged: Vec<PathBuf> = sources
+            .iter()
+            .filter(|path| !changed.contains(path))
+            .cloned()
+            .collect();
+        articles.extend(self::articles(&unchanged, site, permalink, &mut diagnostics).0);
+        diagnostics.check()?;
+        articles.sort_unstable_by(|a, b| (a.date, &a.url).cmp(&(b.date, &b.url)));
+
+        cache.write_listing(
+            "/",
+            layout(
+                &blog_data,
+                theme,
+                "/",
+                &PageMeta::default(),
+                theme.index(&blog_data, &articles),
+            ),
+        )?;
+
+        let newest: Vec<&Article> = articles.iter().rev().collect();
+        for page in pagination::pages(&newest, blog_data.pagination.page_size) {
+            let url = pagination::Page::url(page.number);
+            cache.write_listing(
+                &url,
+                layout(
+                    &blog_data,
+                    theme,
+                    &url,
+                    &page.meta(),
+                    theme.articles_page(&page),
+                ),
+            )?;
+        }
+
+        for kind in taxonomy::Kind::ALL {
+            let taxonomy = taxonomy::Taxonomy::collect(kind, &articles);
+
+            let url = kind.index_url();
+            let page = theme.taxonomy_index(&taxonomy);
+            cache.write_listing(
+                &url,
+                layout(&blog_data, theme, &url, &PageMeta::default(), page),
+            )?;
+            for term in taxonomy.terms.values() {
+                let url = kind.term_url(term.name);
+                let page = theme.term_page(&taxonomy, term);
+                cache.write_listing(
+                    &url,
+                    layout(&blog_data, theme, &url, &PageMeta::default(), page),
+                )?;
+            }
+        }
+
+        let archive = archive::Archive::new(&articles, permalink, &blog_data.lang);
+        cache.write_listing(
+            "/archive/",
+            layout(
+                &blog_data,
+                theme,
+                "/archive/",
+                &PageMeta::default(),
+                theme.archive_index(&archive.years()),
+            ),
+        )?;
+        for (url, meta, page) in archive.pages() {
+            let page = theme.archive_page(&page);
+            cache.write_listing(&url, layout(&blog_data, theme, &url, &meta, page))?;
+        }
+
+        // Links published before URLs used week-based years keep working.
+        let mut legacy = archive.redirects();
+        legacy.extend(
+            articles
+                .iter()
+                .filter_map(|article| Some((article.legacy_url.clone()?, article.url.clone()))),
+        );
+        cache.set_legacy(legacy);
+    }
+
+    let rendered: Vec<(String, String)> = articles
+        .par_iter()
+        .filter(|article| changed.contains(&article.path))
+        .map(|article| {
+            let meta = PageMeta {
+                noindex: article.draft,
+                lang: article.lang.clone(),
+                ..PageMeta::default()
+            };
+            let page = layout(
+                &blog_data,
+                theme,
+                &article.url,
+                &meta,
+                theme.article_page(article, theme::Body::new(&article.document)),
+            );
+            (article.url.clone(), HtmlDocument(page).to_string())
+        })
+        .collect();
+    for (url, html) in rendered {
+        cache.write_html(&url, html)?;
+        summary.articles += 1;
+    }
+
+    for (url, html) in pages_html {
+        cache.write_html(&url, html)?;
+        summary.pages += 1;
+    }
+
+    let redirects = redirect::collect(&blog_data, &site.config_path(), &cache, &mut diagnostics);
+    diagnostics.check()?;
+    for (from, to) in &redirects {
+        cache.write_page(from, redirect::page(&blog_data, theme, to))?;
+    }
+    for file in &blog_data.redirect_files {
+        cache.write(Path::new(file.path()), file.render(&redirects)?)?;
+    }
+
+    summary.assets = assets.len();
+    assets::check(&blog_data, &cache)?;
+
+    cache.prune()?;
+
+    // Broken links only fail checks, builds report them and carry on.
+    let broken = links::check(&cache, &site.config_path())?;
+    if dry_run {
+        for diagnostic in broken {
+            diagnostics.push(diagnostic);
+        }
+        diagnostics.check()?;
+    } else {
+        summary.broken = broken;
+    }
+
+    summary.written = cache.written;
+    summary.unchanged = cache.unchanged;
+    summary.removed = cache.removed;
+    summary.saved = blog_data.minify_html.then_some(cache.saved);
+    summary.scheduled = cache.scheduled();
+    cache.save()?;
+    Ok(summary)
+}
+
+/// Runs the generator as the `blog-generator` binary does with
+/// [`theme::DefaultTheme`], reading the command line. Sites with a look of
+/// their own call this from their own binary with their [`Theme`].
+pub fn run(theme: &'static dyn Theme) -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    if let Some(jobs) = cli.site.jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()?;
+    }
+
+    match cli.command {
+        Command::Build => {
+            let start = Instant::now();
+            let summary = build(&cli.site, theme, false)?;
+            summary.print(start.elapsed());
+            Ok(())
+        }
+        Command::Check => {
+            let summary = build(&cli.site, theme, true)?;
+            println!(
+                "checked {} article(s) and {} page(s), no problems found",
+                summary.articles, summary.pages
+            );
+            Ok(())
+        }
+        Command::Watch => watch::watch(&cli.site, theme, |_| {}),
+        Command::Serve(args) => serve::serve(&cli.site, theme, &args),
+    }
+}
@@ -0,0 +1,168 @@
+use crate::{
+    cache::Cache,
+    diagnostic::Diagnostic,
+    markup::{self, Tag, Token},
+};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+/// Attributes holding URLs, including those of SVG `<use>` elements.
+const ATTRIBUTES: [&str; 3] = ["href", "src", "xlink:href"];
+
+/// Elements whose contents are not markup.
+const RAW: [&str; 2] = ["script", "style"];
+
+fn is_external(url: &str) -> bool {
+    url.starts_with("//")
+        || matches!(url.find(':'), Some(colon) if !url[..colon].contains(['/', '?', '#']))
+}
+
+/// The URL of the page written at `path`.
+fn page_url(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    match path.strip_suffix("index.html") {
+        Some(dir) => format!("/{}", dir),
+        None => format!("/{}", path),
+    }
+}
+
+/// Resolves `link` against the URL of the page it appears on, splitting
+/// off the fragment and dropping the query.
+fn resolve<'a>(base: &str, link: &'a str) -> (String, Option<&'a str>) {
+    let (link, fragment) = match link.split_once('#') {
+        Some((link, fragment)) => (link, Some(fragment)),
+        None => (link, None),
+    };
+    let link = link.split('?').next().unwrap_or_default();
+    if link.is_empty() {
+        return (base.to_string(), fragment);
+    }
+    if link.starts_with('/') {
+        return (link.to_string(), fragment);
+    }
+
+    let mut segments: Vec<&str> = base.split('/').collect();
+    segments.pop();
+    for segment in link.split('/') {
+        match segment {
+            "." => {}
+            ".." if segments.len() > 1 => {
+                segments.pop();
+            }
+            ".." => {}
+            segment => segments.push(segment),
+        }
+    }
+    (segments.join("/"), fragment)
+}
+
+/// The file a local URL is served from, if the output has it.
+fn target(cache: &Cache, url: &str) -> Option<PathBuf> {
+    let path = Path::new(url.trim_start_matches('/'));
+    let candidates = match url.ends_with('/') {
+        true => vec![path.join("index.html")],
+        false => vec![path.to_path_buf(), path.join("index.html")],
+    };
+    candidates.into_iter().find(|path| cache.exists(path))
+}
+
+/// Anchors of a document, from `id` attributes and named links.
+fn anchors(html: &str) -> HashSet<String> {
+    markup::tokens(html, &RAW)
+        .filter_map(|token| match token {
+            Token::Tag(source) => Some(Tag::parse(source)),
+            _ => None,
+        })
+        .flat_map(|tag| {
+            let name = match tag.name.eq_ignore_ascii_case("a") {
+                true => tag.attribute("name"),
+                false => None,
+            };
+            tag.attribute("id").into_iter().chain(name)
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// Finds links between generated files leading to a file or an anchor that
+/// does not exist. Each broken link is reported on the source file of every
+/// page containing it, or on the page itself for listings. Links found on
+/// every page come from the layout, and so are reported once, on `config`.
+pub fn check(cache: &Cache, config: &Path) -> anyhow::Result<Vec<Diagnostic>> {
+    let sources: HashMap<String, &Path> = cache.outputs().collect();
+    let mut pages: Vec<&Path> = cache
+        .files()
+        .into_iter()
+        .filter(|path| path.extension() == Some("html".as_ref()))
+        .collect();
+    pages.sort();
+    let mut total = 0;
+
+    let mut anchors_cache: HashMap<PathBuf, HashSet<String>> = HashMap::new();
+    let mut broken: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for page in pages {
+        let html = String::from_utf8(cache.read(page)?)?;
+        let base = page_url(page);
+        let source = sources.get(&base).copied().unwrap_or(page);
+
+        let mut redirect = false;
+        for token in markup::tokens(&html, &RAW) {
+            let tag = match token {
+                Token::Tag(source) => Tag::parse(source),
+                _ => continue,
+            };
+            redirect |= tag.attribute("http-equiv") == Some("refresh");
+            for attribute in ATTRIBUTES {
+                let link = match tag.attribute(attribute) {
+                    Some(link) => link.replace("&amp;", "&"),
+                    None => continue,
+                };
+                if is_external(&link) {
+                    continue;
+                }
+
+                let (url, fragment) = resolve(&base, &link);
+                let found = match (target(cache, &url), fragment) {
+                    (None, _) => false,
+                    (Some(_), None | Some("")) => true,
+                    (Some(target), Some(fragment)) => {
+                        if !anchors_cache.contains_key(&target) {
+                            let contents = String::from_utf8(cache.read(&target)?)?;
+                            anchors_cache.insert(target.clone(), anchors(&contents));
+                        }
+                        anchors_cache[&target].contains(fragment)
+                    }
+                };
+
+                if !found {
+                    let sources = broken.entry(link).or_default();
+                    if !sources.iter().any(|known| known == source) {
+                        sources.push(source.to_path_buf());
+                    }
+                }
+            }
+        }
+
+        // Redirect pages do not use the layout
+        if !redirect {
+            total += 1;
+        }
+    }
+
+    let mut diagnostics = Vec::new();
+    for (link, sources) in broken {
+        if sources.len() == total && total > 1 {
+            let message = format!("broken link to {} on every page", link);
+            diagnostics.push(Diagnostic::new(config, message));
+        } else {
+            diagnostics.extend(
+                sources
+                    .iter()
+                    .map(|source| Diagnostic::new(source, format!("broken link to {}", link))),
+            );
+        }
+    }
+    Ok(diagnostics)
+}
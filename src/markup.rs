@@ -0,0 +1,234 @@
+/// A piece of a generated HTML document.
+pub enum Token<'a> {
+    Text(&'a str),
+    Tag(&'a str),
+    /// Comments and declarations such as the doctype.
+    Other(&'a str),
+    /// Contents of an element listed as raw, up to its closing tag.
+    Raw(&'a str),
+}
+
+/// Splits `html` into tokens, leaving the contents of `raw` elements as a
+/// single token. This is just enough for pages the generator writes, not a
+/// general HTML parser.
+pub struct Tokens<'a> {
+    rest: &'a str,
+    raw: &'a [&'a str],
+    raw_until: Option<String>,
+}
+
+pub fn tokens<'a>(html: &'a str, raw: &'a [&'a str]) -> Tokens<'a> {
+    Tokens {
+        rest: html,
+        raw,
+        raw_until: None,
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        if self.rest.is_empty() {
+            return None;
+        }
+
+        if let Some(close) = self.raw_until.take() {
+            let end = self
+                .rest
+                .to_ascii_lowercase()
+                .find(&close)
+                .unwrap_or(self.rest.len());
+            let (raw, rest) = self.rest.split_at(end);
+            self.rest = rest;
+            return Some(Token::Raw(raw));
+        }
+
+        let end = match self.rest.find('<') {
+            Some(0) if self.rest.starts_with("<!--") => {
+                self.rest.find("-->").map_or(self.rest.len(), |end| end + 3)
+            }
+            Some(0) => tag_end(self.rest),
+            Some(start) => start,
+            None => self.rest.len(),
+        };
+        let (token, rest) = self.rest.split_at(end);
+        self.rest = rest;
+
+        Some(if !token.starts_with('<') {
+            Token::Text(token)
+        } else if token.starts_with("<!") || token.len() < 3 {
+            Token::Other(token)
+        } else {
+            let name = Tag::parse(token).name.to_ascii_lowercase();
+            if self.raw.contains(&name.as_str()) {
+                self.raw_until = Some(format!("</{}", name));
+            }
+            Token::Tag(token)
+        })
+    }
+}
+
+/// The index right after the tag starting `html`, skipping over `>` in
+/// quoted attribute values.
+fn tag_end(html: &str) -> usize {
+    let mut quote = None;
+    for (index, c) in html.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (None, '>') => return index + 1,
+            _ => {}
+        }
+    }
+    html.len()
+}
+
+pub struct Tag<'a> {
+    /// The element name, starting with `/` for closing tags.
+    pub name: &'a str,
+    /// Attributes with their value as written, without quotes.
+    pub attributes: Vec<(&'a str, Option<&'a str>)>,
+    pub self_closing: bool,
+}
+
+impl<'a> Tag<'a> {
+    pub fn parse(source: &'a str) -> Self {
+        let inner = source.strip_prefix('<').unwrap_or(source);
+        let inner = inner.strip_suffix('>').unwrap_or(inner);
+        let mut rest = inner.trim_start();
+        let name_end = rest
+            .find(|c: char| c.is_ascii_whitespace() || (c == '/' && !rest.starts_with('/')))
+            .unwrap_or(rest.len());
+        let name = &rest[..name_end];
+        rest = &rest[name_end..];
+
+        let mut attributes = Vec::new();
+        let mut self_closing = false;
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                break;
+            }
+            if let Some(after) = rest.strip_prefix('/') {
+                self_closing = true;
+                rest = after;
+                continue;
+            }
+            self_closing = false;
+
+            let name_end = rest
+                .find(|c: char| c.is_ascii_whitespace() || matches!(c, '=' | '/'))
+                .unwrap_or(rest.len());
+            let name = &rest[..name_end];
+            rest = rest[name_end..].trim_start();
+
+            let after = match rest.strip_prefix('=') {
+                Some(after) => after.trim_start(),
+                None => {
+                    attributes.push((name, None));
+                    continue;
+                }
+            };
+            let (value, next) = match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let end = after[1..].find(quote).map_or(after.len(), |end| end + 1);
+                    (&after[1..end], after.get(end + 1..).unwrap_or_default())
+                }
+                _ => after.split_at(
+                    after
+                        .find(|c: char| c.is_ascii_whitespace())
+                        .unwrap_or(after.len()),
+                ),
+            };
+            attributes.push((name, Some(value)));
+            rest = next;
+        }
+
+        Tag {
+            name,
+            attributes,
+            self_closing,
+        }
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&'a str> {
+        self.attributes
+            .iter()
+            .find(|(attribute, _)| attribute.eq_ignore_ascii_case(name))
+            .and_then(|(_, value)| *value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds<'a>(html: &'a str, raw: &'a [&'a str]) -> Vec<(&'static str, &'a str)> {
+        tokens(html, raw)
+            .map(|token| match token {
+                Token::Text(text) => ("text", text),
+                Token::Tag(tag) => ("tag", tag),
+                Token::Other(other) => ("other", other),
+                Token::Raw(raw) => ("raw", raw),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn splits_tags_text_and_comments() {
+        assert_eq!(
+            kinds("<!DOCTYPE html><p title=\"a > b\">x<!-- <p> --></p>", &[]),
+            [
+                ("other", "<!DOCTYPE html>"),
+                ("tag", "<p title=\"a > b\">"),
+                ("text", "x"),
+                ("other", "<!-- <p> -->"),
+                ("tag", "</p>"),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_raw_elements_whole() {
+        assert_eq!(
+            kinds("<PRE><b>  x</b></Pre>y", &["pre"]),
+            [
+                ("tag", "<PRE>"),
+                ("raw", "<b>  x</b>"),
+                ("tag", "</Pre>"),
+                ("text", "y"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_attributes() {
+        let tag = Tag::parse("<a href=\"/a/\" data-x='1' hidden id=b>");
+        assert_eq!(tag.name, "a");
+        assert_eq!(
+            tag.attributes,
+            [
+                ("href", Some("/a/")),
+                ("data-x", Some("1")),
+                ("hidden", None),
+                ("id", Some("b")),
+            ]
+        );
+        assert_eq!(tag.attribute("HREF"), Some("/a/"));
+        assert!(!tag.self_closing);
+    }
+
+    #[test]
+    fn parses_closing_and_self_closing_tags() {
+        assert_eq!(Tag::parse("</div>").name, "/div");
+
+        let tag = Tag::parse("<img src=a.png/>");
+        assert_eq!(tag.name, "img");
+        assert_eq!(tag.attribute("src"), Some("a.png/"));
+
+        let tag = Tag::parse("<br/>");
+        assert_eq!(tag.name, "br");
+        assert!(tag.self_closing);
+    }
+}
@@ -0,0 +1,94 @@
+use crate::permalink::Template;
+use anyhow::bail;
+use dolmen::{prelude::*, Fragment};
+use dolmen_dsl::element as tag;
+
+#[derive(serde::Deserialize)]
+pub struct Item {
+    label: String,
+    /// Where the entry leads, unless it refers to a page.
+    url: Option<String>,
+    /// Slug of the page the entry leads to.
+    page: Option<String>,
+    /// Entries are shown by increasing weight, then in the order given.
+    #[serde(default)]
+    weight: i32,
+    /// Opens the link in a new tab, and never highlights it.
+    #[serde(default)]
+    external: bool,
+}
+
+impl Item {
+    fn url(&self, page_permalink: &Template) -> String {
+        match (&self.url, &self.page) {
+            (Some(url), _) => url.clone(),
+            (None, Some(page)) => page_permalink.render(None, page),
+            (None, None) => unreachable!("menu entries are validated"),
+        }
+    }
+}
+
+/// The `[[menu]]` entries shown in the navigation bar.
+#[derive(serde::Deserialize)]
+#[serde(transparent)]
+pub struct Menu(Vec<Item>);
+
+impl Default for Menu {
+    fn default() -> Self {
+        Menu(vec![
+            Item {
+                label: "Articles".to_string(),
+                url: Some("/articles/".to_string()),
+                page: None,
+                weight: 0,
+                external: false,
+            },
+            Item {
+                label: "About me".to_string(),
+                url: None,
+                page: Some("me".to_string()),
+                weight: 0,
+                external: false,
+            },
+        ])
+    }
+}
+
+impl Menu {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for item in &self.0 {
+            if item.url.is_some() == item.page.is_some() {
+                bail!(
+                    "menu entry `{}` needs either a `url` or a `page`, but not both",
+                    item.label
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The entries as links, marking the one leading to `current` as the
+    /// current page, or as the current section when `current` is below it.
+    pub fn render(&self, page_permalink: &Template, current: &str) -> Fragment {
+        let mut items: Vec<&Item> = self.0.iter().collect();
+        items.sort_by_key(|item| item.weight);
+
+        Fragment::new(items.into_iter().map(|item| {
+            let url = item.url(page_permalink);
+            if item.external {
+                return tag!(a[href: {url}, target: "_blank", rel: "noopener"] {{ &item.label }})
+                    .into_node();
+            }
+
+            match current.strip_prefix(url.as_str()) {
+                Some("") => {
+                    tag!(a[href: {url}, aria-current: "page"] {{ &item.label }}).into_node()
+                }
+                Some(_) if url != "/" && url.ends_with('/') => {
+                    tag!(a[href: {url}, aria-current: "true"] {{ &item.label }}).into_node()
+                }
+                _ => tag!(a[href: {url}] {{ &item.label }}).into_node(),
+            }
+        }))
+    }
+}
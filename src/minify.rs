@@ -0,0 +1,130 @@
+use crate::markup::{self, Tag, Token};
+
+/// Elements whose contents are kept exactly as they are.
+const PRESERVED: [&str; 5] = ["pre", "code", "textarea", "script", "style"];
+
+fn collapse(text: &str, out: &mut String) {
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_ascii_whitespace() {
+            space = true;
+            continue;
+        }
+        if space {
+            out.push(' ');
+            space = false;
+        }
+        out.push(c);
+    }
+    if space {
+        out.push(' ');
+    }
+}
+
+fn needs_quotes(value: &str) -> bool {
+    value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_ascii_whitespace() || matches!(c, '"' | '\'' | '=' | '<' | '>' | '`'))
+}
+
+/// Rewrites a tag with single spaces between attributes, and values only
+/// quoted when they have to.
+fn tag(source: &str, out: &mut String) {
+    let tag = Tag::parse(source);
+    out.push('<');
+    out.push_str(tag.name);
+
+    let mut unquoted = false;
+    for (name, value) in &tag.attributes {
+        out.push(' ');
+        out.push_str(name);
+        unquoted = false;
+
+        if let Some(value) = value {
+            out.push('=');
+            if needs_quotes(value) {
+                let quote = if value.contains('"') { '\'' } else { '"' };
+                out.push(quote);
+                out.push_str(value);
+                out.push(quote);
+            } else {
+                out.push_str(value);
+                unquoted = true;
+            }
+        }
+    }
+    if tag.self_closing {
+        if unquoted {
+            out.push(' ');
+        }
+        out.push('/');
+    }
+    out.push('>');
+}
+
+/// Collapses whitespace in text and tags, leaving the contents of
+/// [`PRESERVED`] elements untouched.
+pub fn html(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    for token in markup::tokens(html, &PRESERVED) {
+        match token {
+            Token::Text(text) => collapse(text, &mut out),
+            Token::Tag(source) => tag(source, &mut out),
+            Token::Other(source) | Token::Raw(source) => out.push_str(source),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collapses_whitespace_in_text() {
+        assert_eq!(
+            html("<p>\n    Some   text\n    here\n</p>\n"),
+            "<p> Some text here </p> "
+        );
+    }
+
+    #[test]
+    fn leaves_preserved_elements_alone() {
+        let source = "<pre>  a\n    b  </pre>\n<textarea name=\"t\">  x\n  y</textarea>";
+        assert_eq!(
+            html(source),
+            "<pre>  a\n    b  </pre> <textarea name=t>  x\n  y</textarea>"
+        );
+        assert_eq!(
+            html("<pre><code>  fn main() {}\n</code></pre>"),
+            "<pre><code>  fn main() {}\n</code></pre>"
+        );
+    }
+
+    #[test]
+    fn unquotes_simple_values() {
+        assert_eq!(
+            html("<a   href=\"/about/\"  class=\"link\">x</a>"),
+            "<a href=/about/ class=link>x</a>"
+        );
+    }
+
+    #[test]
+    fn keeps_quotes_when_needed() {
+        assert_eq!(
+            html("<p class=\"a b\" title=\"\" data-x=\"a=b\">x</p>"),
+            "<p class=\"a b\" title=\"\" data-x=\"a=b\">x</p>"
+        );
+        assert_eq!(
+            html("<p title='say \"hi\"'>x</p>"),
+            "<p title='say \"hi\"'>x</p>"
+        );
+    }
+
+    #[test]
+    fn keeps_boolean_attributes_and_self_closing_tags() {
+        assert_eq!(html("<input  disabled />"), "<input disabled/>");
+        assert_eq!(html("<img src=\"a.png\" />"), "<img src=a.png />");
+    }
+}
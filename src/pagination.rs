@@ -0,0 +1,71 @@
+use crate::{Article, PageMeta};
+
+#[derive(serde::Deserialize)]
+#[serde(default)]
+pub struct Pagination {
+    pub page_size: usize,
+    pub latest: usize,
+}
+
+impl Default for Pagination {
+    fn default() -> Self {
+        Pagination {
+            page_size: 10,
+            latest: 5,
+        }
+    }
+}
+
+pub struct Page<'a> {
+    pub number: usize,
+    pub count: usize,
+    pub articles: &'a [&'a Article],
+}
+
+impl Page<'_> {
+    pub fn url(number: usize) -> String {
+        match number {
+            1 => "/articles/".to_string(),
+            number => format!("/articles/page/{}/", number),
+        }
+    }
+
+    pub fn prev(&self) -> Option<usize> {
+        Some(self.number - 1).filter(|&number| number >= 1)
+    }
+
+    pub fn next(&self) -> Option<usize> {
+        Some(self.number + 1).filter(|&number| number <= self.count)
+    }
+
+    pub fn meta(&self) -> PageMeta {
+        PageMeta {
+            prev: self.prev().map(Page::url),
+            next: self.next().map(Page::url),
+            ..PageMeta::default()
+        }
+    }
+}
+
+/// URLs of the listing pages for the given number of articles.
+pub fn urls(articles: usize, page_size: usize) -> impl Iterator<Item = String> {
+    (1..=articles.div_ceil(page_size).max(1)).map(Page::url)
+}
+
+pub fn pages<'a>(articles: &'a [&'a Article], page_size: usize) -> Vec<Page<'a>> {
+    let mut chunks: Vec<_> = articles.chunks(page_size).collect();
+    if chunks.is_empty() {
+        chunks.push(&[]);
+    }
+
+    let count = chunks.len();
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, articles)| Page {
+            number: index + 1,
+            count,
+            articles,
+        })
+        .collect()
+}
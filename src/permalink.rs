@@ -0,0 +1,370 @@
+use crate::diagnostic::{Diagnostic, Diagnostics};
+use anyhow::bail;
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+use time::Date;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Year,
+    Month,
+    Week,
+    Day,
+    Slug,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Field> {
+        match name {
+            "year" => Some(Field::Year),
+            "month" => Some(Field::Month),
+            "week" => Some(Field::Week),
+            "day" => Some(Field::Day),
+            "slug" => Some(Field::Slug),
+            _ => None,
+        }
+    }
+
+    fn value(self, date: Option<Date>, slug: &str) -> String {
+        match (self, date) {
+            (Field::Slug, _) => slug.to_string(),
+            (Field::Year, Some(date)) => format!("{:04}", date.year()),
+            (Field::Month, Some(date)) => format!("{:02}", date.month() as u8),
+            (Field::Week, Some(date)) => format!("{:02}", date.iso_week()),
+            (Field::Day, Some(date)) => format!("{:02}", date.day()),
+            (_, None) => String::new(),
+        }
+    }
+}
+
+enum Part {
+    Literal(String),
+    Field(Field),
+}
+
+/// A permalink pattern such as `/{year}/{week}/{slug}/`, used both for the
+/// links pointing to a document and for the path it is written to.
+#[derive(serde::Deserialize)]
+#[serde(try_from = "String")]
+pub struct Template {
+    source: String,
+    parts: Vec<Part>,
+}
+
+impl TryFrom<String> for Template {
+    type Error = anyhow::Error;
+
+    fn try_from(source: String) -> anyhow::Result<Self> {
+        if !source.starts_with('/') || !source.ends_with('/') {
+            bail!("permalink `{}` must start and end with `/`", source);
+        }
+
+        let mut parts = Vec::new();
+        let mut rest = source.as_str();
+        while let Some(start) = rest.find('{') {
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => bail!("permalink `{}` has an unclosed `{{`", source),
+            };
+            let name = &rest[start + 1..end];
+            let field = match Field::parse(name) {
+                Some(field) => field,
+                None => bail!(
+                    "permalink `{}` uses unknown placeholder `{{{}}}`, expected one of \
+                     {{year}}, {{month}}, {{week}}, {{day}} or {{slug}}",
+                    source,
+                    name
+                ),
+            };
+
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            parts.push(Part::Field(field));
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+
+        let template = Template { source, parts };
+        if !template.uses(Field::Slug) {
+            bail!("permalink `{}` must contain {{slug}}", template.source);
+        }
+        Ok(template)
+    }
+}
+
+impl Template {
+    fn uses(&self, field: Field) -> bool {
+        self.parts
+            .iter()
+            .any(|part| matches!(part, Part::Field(used) if *used == field))
+    }
+
+    fn is_dated(&self) -> bool {
+        [Field::Year, Field::Month, Field::Week, Field::Day]
+            .into_iter()
+            .any(|field| self.uses(field))
+    }
+
+    fn value(&self, field: Field, date: Option<Date>, slug: &str) -> String {
+        match (field, date) {
+            // ISO weeks belong to the week-based year, which differs from the
+            // calendar year for the first and last few days of some years.
+            (Field::Year, Some(date)) if self.uses(Field::Week) => {
+                format!("{:04}", date.to_iso_week_date().0)
+            }
+            _ => field.value(date, slug),
+        }
+    }
+
+    fn render_with(&self, value: impl Fn(Field) -> String) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(literal) => literal.clone(),
+                Part::Field(field) => value(*field),
+            })
+            .collect()
+    }
+
+    pub fn render(&self, date: Option<Date>, slug: &str) -> String {
+        self.render_with(|field| self.value(field, date, slug))
+    }
+
+    /// The URL earlier versions generated by pairing the calendar year with
+    /// the ISO week, when it differs from the correct one.
+    pub fn legacy(&self, date: Date, slug: &str) -> Option<String> {
+        let legacy = self.render_with(|field| field.value(Some(date), slug));
+        Some(legacy).filter(|legacy| *legacy != self.render(Some(date), slug))
+    }
+
+    /// The date placeholders making up whole leading path segments, starting
+    /// with `{year}`. Each prefix of those gets its own archive page.
+    pub fn archive_fields(&self) -> Vec<Field> {
+        let mut fields = Vec::new();
+        for segment in self.source.split('/').filter(|segment| !segment.is_empty()) {
+            match segment
+                .strip_prefix('{')
+                .and_then(|name| name.strip_suffix('}'))
+                .and_then(Field::parse)
+            {
+                Some(Field::Year) if fields.is_empty() => fields.push(Field::Year),
+                Some(Field::Slug) | None => break,
+                Some(field) if !fields.is_empty() => fields.push(field),
+                Some(_) => break,
+            }
+        }
+        fields
+    }
+
+    pub fn archive_values(&self, date: Date) -> Vec<String> {
+        self.archive_fields()
+            .into_iter()
+            .map(|field| self.value(field, Some(date), ""))
+            .collect()
+    }
+
+    /// Like [`Template::legacy`], for archive pages.
+    pub fn legacy_archive_values(&self, date: Date) -> Vec<String> {
+        self.archive_fields()
+            .into_iter()
+            .map(|field| field.value(Some(date), ""))
+            .collect()
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(default)]
+pub struct Permalinks {
+    pub article: Template,
+    pub page: Template,
+}
+
+impl Default for Permalinks {
+    fn default() -> Self {
+        Permalinks {
+            article: Template::try_from("/{year}/{week}/{slug}/".to_string()).unwrap(),
+            page: Template::try_from("/{slug}/".to_string()).unwrap(),
+        }
+    }
+}
+
+impl Permalinks {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.page.is_dated() {
+            bail!(
+                "page permalink `{}` can only use {{slug}}, pages have no date",
+                self.page.source
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Whether `segment` can be used as a single segment of a URL, so that
+/// the page it names stays inside the output directory.
+pub fn is_segment(segment: &str) -> bool {
+    !segment.is_empty() && !matches!(segment, "." | "..") && !segment.contains(['/', '\\'])
+}
+
+/// Reports sources that would be written to the same path, either as
+/// another source or as one of the `generated` listing pages and feeds.
+pub fn check_unique<'a>(
+    urls: impl IntoIterator<Item = (String, &'a Path)>,
+    generated: &HashSet<String>,
+    diagnostics: &mut Diagnostics,
+) {
+    // Feeds are files, which a directory of the same name would replace.
+    let generated: HashSet<&str> = generated.iter().map(|url| url.trim_matches('/')).collect();
+    let mut seen: HashMap<String, &Path> = HashMap::new();
+    for (url, path) in urls {
+        if generated.contains(url.trim_matches('/')) {
+            diagnostics.push(Diagnostic::field(
+                path,
+                "slug",
+                format!(
+                    "maps to {}, where the blog writes its own listing or feed, set a different `slug`",
+                    url
+                ),
+            ));
+        } else if let Some(other) = seen.get(&url) {
+            diagnostics.push(Diagnostic::field(
+                path,
+                "slug",
+                format!(
+                    "maps to {} like {}, set a different `slug` in one of them",
+                    url,
+                    other.display()
+                ),
+            ));
+        } else {
+            seen.insert(url, path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::Month;
+
+    fn template(source: &str) -> anyhow::Result<Template> {
+        Template::try_from(source.to_string())
+    }
+
+    fn date(year: i32, month: u8, day: u8) -> Date {
+        Date::from_calendar_date(year, Month::try_from(month).unwrap(), day).unwrap()
+    }
+
+    #[test]
+    fn parses_valid_templates() {
+        for source in [
+            "/{slug}/",
+            "/{year}/{week}/{slug}/",
+            "/blog/{year}-{month}-{day}/{slug}/",
+        ] {
+            assert_eq!(template(source).unwrap().source, source);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        for source in [
+            "{slug}/",
+            "/{slug}",
+            "/{year}/",
+            "/{slug/",
+            "/{hour}/{slug}/",
+        ] {
+            assert!(template(source).is_err(), "{} was accepted", source);
+        }
+    }
+
+    #[test]
+    fn renders_fields() {
+        let template = template("/{year}/{month}/{day}/{slug}/").unwrap();
+        assert_eq!(
+            template.render(Some(date(2022, 3, 7)), "post"),
+            "/2022/03/07/post/"
+        );
+
+        let template = self::template("/{slug}/").unwrap();
+        assert_eq!(template.render(None, "me"), "/me/");
+    }
+
+    #[test]
+    fn renders_weeks_with_week_based_year() {
+        let template = template("/{year}/{week}/{slug}/").unwrap();
+        assert_eq!(template.render(Some(date(2022, 1, 11)), "a"), "/2022/02/a/");
+        // Friday 2021-01-01 is in the last week of 2020.
+        assert_eq!(template.render(Some(date(2021, 1, 1)), "a"), "/2020/53/a/");
+        // Monday 2019-12-30 is in the first week of 2020.
+        assert_eq!(
+            template.render(Some(date(2019, 12, 30)), "a"),
+            "/2020/01/a/"
+        );
+    }
+
+    #[test]
+    fn legacy_urls_use_calendar_year() {
+        let template = template("/{year}/{week}/{slug}/").unwrap();
+        assert_eq!(
+            template.legacy(date(2021, 1, 1), "a").as_deref(),
+            Some("/2021/53/a/")
+        );
+        assert_eq!(
+            template.legacy(date(2019, 12, 30), "a").as_deref(),
+            Some("/2019/01/a/")
+        );
+    }
+
+    #[test]
+    fn legacy_urls_only_when_they_differ() {
+        let template = template("/{year}/{week}/{slug}/").unwrap();
+        assert_eq!(template.legacy(date(2022, 1, 11), "a"), None);
+
+        let template = self::template("/{year}/{month}/{slug}/").unwrap();
+        assert_eq!(template.legacy(date(2021, 1, 1), "a"), None);
+    }
+
+    #[test]
+    fn archive_values_follow_urls() {
+        let template = template("/{year}/{week}/{slug}/").unwrap();
+        assert_eq!(template.archive_values(date(2021, 1, 1)), ["2020", "53"]);
+        assert_eq!(
+            template.legacy_archive_values(date(2021, 1, 1)),
+            ["2021", "53"]
+        );
+    }
+
+    #[test]
+    fn keeps_calendar_year_without_weeks() {
+        let template = template("/{year}/{month}/{slug}/").unwrap();
+        assert_eq!(template.render(Some(date(2021, 1, 1)), "a"), "/2021/01/a/");
+        assert_eq!(
+            template.render(Some(date(2019, 12, 30)), "a"),
+            "/2019/12/a/"
+        );
+    }
+
+    #[test]
+    fn rejects_generated_urls() {
+        let generated = HashSet::from(["/archive/".to_string(), "/atom.xml".to_string()]);
+        let check = |url: &str| {
+            let mut diagnostics = Diagnostics::default();
+            check_unique(
+                [(url.to_string(), Path::new("pages/a.px"))],
+                &generated,
+                &mut diagnostics,
+            );
+            diagnostics.check().is_ok()
+        };
+        assert!(!check("/archive/"));
+        assert!(!check("/atom.xml/"));
+        assert!(check("/about/"));
+    }
+}
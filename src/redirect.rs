@@ -0,0 +1,133 @@
+use crate::{
+    cache::Cache,
+    diagnostic::{Diagnostic, Diagnostics},
+    permalink,
+    theme::Theme,
+    BlogData,
+};
+use dolmen::Fragment;
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+/// Server-side redirect rules, for hosts that can read them.
+#[derive(Clone, Copy, serde::Deserialize)]
+pub enum RuleFile {
+    /// `_redirects`, as read by Netlify and Cloudflare Pages.
+    #[serde(rename = "_redirects")]
+    Redirects,
+    /// `redirects.nginx.conf`, to be included in an nginx `server` block.
+    #[serde(rename = "nginx")]
+    Nginx,
+}
+
+impl RuleFile {
+    pub fn path(self) -> &'static str {
+        match self {
+            RuleFile::Redirects => "_redirects",
+            RuleFile::Nginx => "redirects.nginx.conf",
+        }
+    }
+
+    pub fn render(self, redirects: &BTreeMap<String, String>) -> anyhow::Result<String> {
+        let mut out = String::new();
+        for (from, to) in redirects {
+            match self {
+                RuleFile::Redirects => writeln!(out, "{} {} 301", from, to)?,
+                RuleFile::Nginx => writeln!(out, "location = {} {{ return 301 {}; }}", from, to)?,
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Aliases are directory URLs, like the pages they replace.
+pub fn normalize(alias: &str) -> String {
+    match alias.trim_matches('/') {
+        "" => "/".to_string(),
+        alias => format!("/{}/", alias),
+    }
+}
+
+/// Whether `alias` names a page inside the output directory, that is
+/// without `.` or `..` segments.
+pub fn is_valid(alias: &str) -> bool {
+    match alias.trim_matches('/') {
+        "" => true,
+        alias => alias.split('/').all(permalink::is_segment),
+    }
+}
+
+/// Gathers the redirects from `blog.toml`, from source aliases and from
+/// URLs generated by earlier versions. Explicit redirects must not shadow
+/// real content nor each other, and are reported in `diagnostics` when they
+/// do. Legacy ones are dropped when they would.
+pub fn collect(
+    blog_data: &BlogData,
+    config: &Path,
+    cache: &Cache,
+    diagnostics: &mut Diagnostics,
+) -> BTreeMap<String, String> {
+    let mut aliases = Vec::new();
+    for (from, to) in &blog_data.redirects {
+        match is_valid(from) {
+            true => aliases.push((from.as_str(), to.as_str(), config, "redirects")),
+            false => diagnostics.push(Diagnostic::field(
+                config,
+                "redirects",
+                format!("alias {} must not contain `.` or `..` segments", from),
+            )),
+        }
+    }
+    // Aliases from source metadata were checked when reading them.
+    aliases.extend(
+        cache
+            .aliases()
+            .map(|(from, to, path)| (from, to, path, "aliases")),
+    );
+
+    let mut explicit: BTreeMap<String, (String, &Path)> = BTreeMap::new();
+    for (from, to, path, field) in aliases {
+        let from = normalize(from);
+        let problem = if cache.is_content(&from) {
+            Some(format!("alias {} collides with existing content", from))
+        } else if from == to {
+            Some(format!("alias {} redirects to itself", from))
+        } else {
+            match explicit.get(&from) {
+                Some((other_to, other)) if other_to != to => Some(format!(
+                    "alias {} redirects to {} here but to {} in {}",
+                    from,
+                    to,
+                    other_to,
+                    other.display()
+                )),
+                _ => None,
+            }
+        };
+        match problem {
+            Some(message) => diagnostics.push(Diagnostic::field(path, field, message)),
+            None => {
+                explicit.insert(from, (to.to_string(), path));
+            }
+        }
+    }
+
+    let mut redirects: BTreeMap<String, String> = cache
+        .legacy()
+        .iter()
+        .filter(|(from, _)| !cache.is_content(from))
+        .map(|(from, to)| (from.clone(), to.clone()))
+        .collect();
+    redirects.extend(explicit.into_iter().map(|(from, (to, _))| (from, to)));
+    redirects
+}
+
+/// A static page sending visitors and crawlers from an old URL to `target`.
+/// The canonical link is absolute when the site has a `base_url`, relative
+/// otherwise.
+pub fn page(blog_data: &BlogData, theme: &dyn Theme, target: &str) -> Fragment {
+    let canonical = match (target.starts_with('/'), blog_data.absolute_url(target)) {
+        (true, Ok(url)) => url,
+        _ => target.to_string(),
+    };
+    theme.redirect(blog_data, target, &canonical)
+}
@@ -0,0 +1,240 @@
+use crate::{theme::Theme, watch, SiteArgs};
+use clap::Args;
+use std::{
+    fs, io,
+    net::SocketAddr,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+};
+use tiny_http::{Header, Request, Response, Server};
+
+const RELOAD_PATH: &str = "/__livereload";
+
+// Polls the build version and reloads the page once it changes.
+const RELOAD_SCRIPT: &str = r#"<script>
+(function () {
+    var version = null;
+    setInterval(function () {
+        fetch("/__livereload").then(function (response) {
+            return response.text();
+        }).then(function (current) {
+            if (version !== null && version !== current) {
+                location.reload();
+            }
+            version = current;
+        }).catch(function () {});
+    }, 1000);
+})();
+</script>"#;
+
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Address to listen on
+    #[clap(long, default_value = "127.0.0.1:8000")]
+    address: SocketAddr,
+}
+
+#[derive(Default)]
+struct Status {
+    version: u64,
+    error: Option<String>,
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("xml") => "application/xml",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        Some("woff2") => "font/woff2",
+        Some("txt") => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+fn inject_reload(html: &str) -> String {
+    match html.rfind("</body>") {
+        Some(index) => format!("{}{}{}", &html[..index], RELOAD_SCRIPT, &html[index..]),
+        None => format!("{}{}", html, RELOAD_SCRIPT),
+    }
+}
+
+fn error_page(error: &str) -> String {
+    let error = error
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    inject_reload(&format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Build failed</title></head>\
+         <body><h1>Build failed</h1><pre>{}</pre></body></html>",
+        error
+    ))
+}
+
+enum Resolved {
+    File(PathBuf),
+    Redirect(String),
+    NotFound,
+}
+
+/// Decodes the `%XX` escapes of a request path, which browsers use for
+/// anything but ASCII letters, digits and a few symbols.
+fn percent_decode(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'%' => {
+                let hex = bytes
+                    .get(index + 1..index + 3)
+                    .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))?;
+                decoded.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+                index += 3;
+            }
+            byte => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+fn resolve(output_dir: &Path, url: &str) -> Resolved {
+    let path = url.split(['?', '#']).next().unwrap_or("/");
+    let decoded = match percent_decode(path) {
+        Some(decoded) => decoded,
+        None => return Resolved::NotFound,
+    };
+    let relative = Path::new(decoded.trim_start_matches('/'));
+    if relative
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return Resolved::NotFound;
+    }
+
+    let target = output_dir.join(relative);
+    if target.is_dir() {
+        if !path.ends_with('/') {
+            return Resolved::Redirect(format!("{}/", path));
+        }
+        let index = target.join("index.html");
+        if index.is_file() {
+            return Resolved::File(index);
+        }
+    } else if target.is_file() {
+        return Resolved::File(target);
+    }
+
+    Resolved::NotFound
+}
+
+fn respond(request: Request, output_dir: &Path, status: &Mutex<Status>) -> io::Result<()> {
+    if request.url() == RELOAD_PATH {
+        let version = status.lock().unwrap().version.to_string();
+        return request.respond(Response::from_string(version));
+    }
+
+    let (path, code) = match resolve(output_dir, request.url()) {
+        Resolved::File(path) => (path, 200),
+        Resolved::Redirect(location) => {
+            return request
+                .respond(Response::empty(301).with_header(header("Location", &location)));
+        }
+        Resolved::NotFound => (output_dir.join("404.html"), 404),
+    };
+
+    let is_html = path.extension() == Some("html".as_ref());
+    if is_html {
+        if let Some(error) = &status.lock().unwrap().error {
+            return request.respond(
+                Response::from_string(error_page(error))
+                    .with_status_code(500)
+                    .with_header(header("Content-Type", content_type(&path))),
+            );
+        }
+    }
+
+    match fs::read(&path) {
+        Ok(contents) if is_html => request.respond(
+            Response::from_string(inject_reload(&String::from_utf8_lossy(&contents)))
+                .with_status_code(code)
+                .with_header(header("Content-Type", content_type(&path))),
+        ),
+        Ok(contents) => request.respond(
+            Response::from_data(contents)
+                .with_status_code(code)
+                .with_header(header("Content-Type", content_type(&path))),
+        ),
+        Err(_) => request.respond(Response::from_string("Not found").with_status_code(404)),
+    }
+}
+
+pub fn serve(site: &SiteArgs, theme: &'static dyn Theme, args: &ServeArgs) -> anyhow::Result<()> {
+    let status = Arc::new(Mutex::new(Status::default()));
+
+    {
+        let site = site.clone();
+        let status = status.clone();
+        thread::spawn(move || {
+            let result = watch::watch(&site, theme, |result| {
+                let mut status = status.lock().unwrap();
+                status.version += 1;
+                status.error = result.as_ref().err().map(|error| format!("{:?}", error));
+            });
+            if let Err(error) = result {
+                eprintln!("error: watcher stopped: {:?}", error);
+            }
+        });
+    }
+
+    let server = Server::http(args.address).map_err(|error| anyhow::anyhow!(error))?;
+    println!(
+        "serving {} on http://{}/",
+        site.output.display(),
+        args.address
+    );
+
+    for request in server.incoming_requests() {
+        if let Err(error) = respond(request, &site.output, &status) {
+            eprintln!("error: failed to respond: {}", error);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_decode_decodes_utf8() {
+        assert_eq!(
+            percent_decode("/tags/caf%C3%A9/").as_deref(),
+            Some("/tags/café/")
+        );
+        assert_eq!(percent_decode("/a%20b").as_deref(), Some("/a b"));
+    }
+
+    #[test]
+    fn percent_decode_rejects_invalid_escapes() {
+        assert_eq!(percent_decode("/caf%C3"), None);
+        assert_eq!(percent_decode("/a%+1"), None);
+        assert_eq!(percent_decode("/a%2"), None);
+    }
+}
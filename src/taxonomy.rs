@@ -0,0 +1,168 @@
+use crate::{
+    diagnostic::{Diagnostic, Diagnostics},
+    feed::Entry,
+    Article,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    path::Path,
+};
+
+#[derive(Clone, Copy)]
+pub enum Kind {
+    Tags,
+    Categories,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 2] = [Kind::Tags, Kind::Categories];
+
+    pub fn path(self) -> &'static str {
+        match self {
+            Kind::Tags => "tags",
+            Kind::Categories => "categories",
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Kind::Tags => "Tags",
+            Kind::Categories => "Categories",
+        }
+    }
+
+    /// The metadata field terms are read from.
+    fn field(self) -> &'static str {
+        match self {
+            Kind::Tags => "tags",
+            Kind::Categories => "category",
+        }
+    }
+
+    fn names<'a>(self, tags: &'a [String], category: &'a Option<String>) -> Vec<&'a str> {
+        match self {
+            Kind::Tags => tags.iter().map(String::as_str).collect(),
+            Kind::Categories => category.iter().map(String::as_str).collect(),
+        }
+    }
+
+    pub fn terms(self, article: &Article) -> Vec<&str> {
+        self.names(&article.tags, &article.category)
+    }
+
+    pub fn index_url(self) -> String {
+        format!("/{}/", self.path())
+    }
+
+    pub fn term_url(self, name: &str) -> String {
+        format!("/{}/{}/", self.path(), slug(name))
+    }
+}
+
+pub fn slug(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    while slug.ends_with('-') {
+        slug.pop();
+    }
+    slug
+}
+
+/// URLs of the taxonomy pages listing `entries`.
+pub fn urls(entries: &[&Entry]) -> BTreeSet<String> {
+    let mut urls = BTreeSet::new();
+    for kind in Kind::ALL {
+        urls.insert(kind.index_url());
+        for entry in entries {
+            let names = kind.names(&entry.tags, &entry.category);
+            urls.extend(names.into_iter().map(|name| kind.term_url(name)));
+        }
+    }
+    urls
+}
+
+pub struct Term<'a> {
+    pub name: &'a str,
+    pub articles: Vec<&'a Article>,
+}
+
+pub struct Taxonomy<'a> {
+    pub kind: Kind,
+    pub terms: BTreeMap<String, Term<'a>>,
+}
+
+impl<'a> Taxonomy<'a> {
+    /// Groups the articles by term, listing each article once per term even
+    /// when it repeats it. Names are expected to have gone through
+    /// [`check_names`] and [`check_collisions`] already.
+    pub fn collect(kind: Kind, articles: &'a [Article]) -> Self {
+        let mut terms: BTreeMap<String, Term<'a>> = BTreeMap::new();
+        for article in articles {
+            let mut seen = HashSet::new();
+            for name in kind.terms(article) {
+                let slug = slug(name);
+                if !seen.insert(slug.clone()) {
+                    continue;
+                }
+                terms
+                    .entry(slug)
+                    .or_insert_with(|| Term {
+                        name,
+                        articles: Vec::new(),
+                    })
+                    .articles
+                    .push(article);
+            }
+        }
+
+        Taxonomy { kind, terms }
+    }
+}
+
+/// Fails unless each of the given terms of a source has a page to be
+/// listed on.
+pub fn check_names(path: &Path, field: &'static str, names: &[&str]) -> Result<(), Diagnostic> {
+    match names.iter().find(|name| slug(name).is_empty()) {
+        Some(name) => Err(Diagnostic::field(
+            path,
+            field,
+            format!("`{}` does not contain any letter or digit", name),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Reports terms spelled differently in different sources but mapping to
+/// the same page, such as `Rust` and `rust`.
+pub fn check_collisions<'a>(
+    entries: impl IntoIterator<Item = (&'a Path, &'a Entry)> + Clone,
+    diagnostics: &mut Diagnostics,
+) {
+    for kind in Kind::ALL {
+        let mut seen: BTreeMap<String, (&str, &Path)> = BTreeMap::new();
+        for (path, entry) in entries.clone() {
+            for name in kind.names(&entry.tags, &entry.category) {
+                let (other, other_path) = *seen.entry(slug(name)).or_insert((name, path));
+                if other != name {
+                    diagnostics.push(Diagnostic::field(
+                        path,
+                        kind.field(),
+                        format!(
+                            "`{}` collides with `{}` used by {} (both map to {})",
+                            name,
+                            other,
+                            other_path.display(),
+                            kind.term_url(name)
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+}
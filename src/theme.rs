@@ -0,0 +1,457 @@
+use crate::{
+    archive::{Period, PeriodPage},
+    assets, pagination,
+    taxonomy::{Kind, Taxonomy, Term},
+    Article, BlogData, PageMeta, DATE_FORMAT,
+};
+use dolmen::{prelude::*, Fragment};
+use dolmen_dsl::element as tag;
+use pastex::{document::Document, output::html};
+use std::iter::once;
+
+/// A page being rendered, as given to the layout.
+pub struct Page<'a> {
+    pub site: &'a BlogData,
+    pub url: &'a str,
+    pub meta: &'a PageMeta,
+    /// Language of the page, that of the site unless its source sets one.
+    pub lang: &'a str,
+}
+
+/// The rendered contents of a source, along with its summary if it has one.
+pub struct Body {
+    pub contents: Fragment,
+    pub summary: Option<Fragment>,
+}
+
+impl Body {
+    pub fn new(document: &Document) -> Self {
+        let (contents, summary) = html::output(document);
+        Body { contents, summary }
+    }
+}
+
+/// Renders the markup of the generated pages.
+///
+/// Every hook defaults to the markup of the default theme, so that a theme
+/// only overrides the parts it changes.
+pub trait Theme: Sync {
+    /// Wraps the contents of every page with the document head, navigation
+    /// and footer.
+    fn layout(&self, page: &Page, inner: Fragment) -> Fragment {
+        let site = page.site;
+        let footer = html::output_fragment(&pastex::document::process_fragment(&site.footer));
+        let menu = site.menu.render(&site.permalinks.page, page.url);
+        let socials = Fragment::new(site.socials.iter().map(|social| {
+            tag!(a[href: {social.url.clone()}, target: "_blank", title: {social.name.clone()}] {
+                svg[xmlns: "http://www.w3.org/2000/svg", viewbox: "0 0 16 16", alt: {social.name.clone()}] {
+                    use[href: {format!("{}#{}", site.asset_url(assets::ICONS), social.icon_name)}]
+                };
+                span {{ &social.name }};
+            })
+            .into_node()
+        }));
+        let stylesheets = Fragment::new(site.stylesheets.iter().map(|stylesheet| {
+            tag!(link[rel: "stylesheet", type: "text/css", href: {site.asset_url(stylesheet)}])
+                .into_node()
+        }));
+        let noindex = if page.meta.noindex {
+            tag!(meta[name: "robots", content: "noindex"]).into_node()
+        } else {
+            Fragment::empty().into_node()
+        };
+        let relations = Fragment::new(
+            [("prev", &page.meta.prev), ("next", &page.meta.next)]
+                .into_iter()
+                .filter_map(|(rel, href)| {
+                    href.as_ref()
+                        .map(|href| tag!(link[rel: {rel}, href: {href.clone()}]).into_node())
+                }),
+        );
+        let feeds = Fragment::new(site.feeds.enabled().map(|format| {
+            tag!(link[rel: "alternate", type: {format.mime_type()}, title: {format!("{} ({})", site.title, format.name())}, href: {format.path()}]).into_node()
+        }));
+
+        let html = tag!(html[lang: { page.lang.to_string() }] {
+            head {
+                meta[charset: "utf-8"];
+                meta[name: "viewport", content: "width=device-width, initial-scale=1"];
+                { noindex };
+                title {{ &site.title }};
+                { stylesheets };
+                { feeds };
+                { relations };
+            }
+            body {
+                nav {
+                    div[class: "bl-wrapper"] {
+                        a[href: "/"] {{ &site.title }};
+                        { menu };
+                        span[class: "bl-separator"] {{ Fragment::empty() }};
+                        { socials };
+                    };
+                    div[class: "ua ua-blue"] {{ "" }};
+                    div[class: "ua ua-yellow"] {{ "" }};
+                }
+                { inner };
+                footer {
+                    div[class: "bl-wrapper"] {
+                        { separator() };
+                        { footer };
+                    }
+                };
+            }
+        });
+        Fragment::new(once(html.into_node()))
+    }
+
+    /// The home page, given every published article, oldest first.
+    fn index(&self, site: &BlogData, articles: &[Article]) -> Fragment {
+        let tagline = html::output_fragment(&pastex::document::process_fragment(&site.tagline));
+        let latest = site.pagination.latest;
+        let see_all = if articles.len() > latest {
+            tag!(p[class: "bl-see-all"] {
+                a[href: "/articles/"] {{ "See all articles" }};
+            })
+            .into_node()
+        } else {
+            Fragment::empty().into_node()
+        };
+        let newest: Vec<&Article> = articles.iter().rev().take(latest).collect();
+
+        Fragment::new([
+            tag!(main {
+                div[class: "bl-main-wrapper"] {
+                    header[class: "bl-home"] {
+                        h1 {{ &site.title }};
+                        p {{ tagline }};
+                    }
+                }
+            })
+            .into_node(),
+            tag!(div[class: "bl-main-wrapper"] {
+                header {
+                    h2 {{ "Latest articles" }};
+                }
+                { self.article_list(&newest) };
+                { see_all };
+            })
+            .into_node(),
+        ])
+    }
+
+    fn article_page(&self, article: &Article, body: Body) -> Fragment {
+        let title = article.title.clone();
+
+        let tag = tag!(main[class: "bl-main-wrapper"] {
+            header {
+                { draft_banner(article) };
+                p {{ article.date.format(&DATE_FORMAT).unwrap() }};
+                h1 {{ title }};
+                { taxonomy_links(article) };
+            }
+            { body.summary.map(|summary| {
+                tag!(div[class: "bl-abstract"] {
+                    { summary };
+                    { separator() };
+                }).into_node()
+            }).unwrap_or_else(|| Fragment::empty().into_node()) };
+            { body.contents };
+        });
+
+        Fragment::new(once(tag.into_node()))
+    }
+
+    fn article_preview(&self, article: &Article, body: Body) -> Box<dyn Node> {
+        let title = article.title.clone();
+
+        tag!(article[class: "bl-article-preview"] {
+            a[href: { article.url.clone() }] {
+                { draft_banner(article) };
+                p {{ article.date.format(&DATE_FORMAT).unwrap() }};
+                h3 {{ title }};
+            };
+            { taxonomy_links(article) };
+            { body.summary.map(|block| tag!(div {{ block }}).into_node()).unwrap_or_else(|| Fragment::empty().into_node()) };
+        }).into_node()
+    }
+
+    /// Previews of the given articles, in the order they are listed in,
+    /// as shown on the home page and on listing pages.
+    fn article_list(&self, articles: &[&Article]) -> Fragment {
+        Fragment::new(
+            articles
+                .iter()
+                .map(|article| self.article_preview(article, Body::new(&article.document))),
+        )
+    }
+
+    /// A page of the full list of articles.
+    fn articles_page(&self, page: &pagination::Page) -> Fragment {
+        let articles = self.article_list(page.articles);
+
+        Fragment::new(once(
+            tag!(div[class: "bl-main-wrapper"] {
+                { articles };
+                { self.pagination(page) };
+            })
+            .into_node(),
+        ))
+    }
+
+    /// Links to the other pages of the full list of articles.
+    fn pagination(&self, page: &pagination::Page) -> Box<dyn Node> {
+        if page.count <= 1 {
+            return Fragment::empty().into_node();
+        }
+
+        let link = |number: Option<usize>, rel: &'static str, label: &'static str| match number {
+            Some(number) => {
+                tag!(a[href: { pagination::Page::url(number) }, rel: {rel}] {{ label }}).into_node()
+            }
+            None => tag!(span[class: "bl-disabled"] {{ label }}).into_node(),
+        };
+        let numbers = Fragment::new((1..=page.count).map(|number| {
+            if number == page.number {
+                tag!(span[aria-current: "page"] {{ number.to_string() }}).into_node()
+            } else {
+                tag!(a[href: { pagination::Page::url(number) }] {{ number.to_string() }})
+                    .into_node()
+            }
+        }));
+
+        tag!(nav[class: "bl-pagination", aria-label: "Pagination"] {
+            { link(page.prev(), "prev", "Previous") };
+            { numbers };
+            { link(page.next(), "next", "Next") };
+        })
+        .into_node()
+    }
+
+    /// Every term of a taxonomy, such as every tag.
+    fn taxonomy_index(&self, taxonomy: &Taxonomy) -> Fragment {
+        let terms = Fragment::new(taxonomy.terms.values().map(|term| {
+            tag!(li {
+                a[href: { taxonomy.kind.term_url(term.name) }] {{ term.name }};
+                span {{ format!(" ({})", term.articles.len()) }};
+            })
+            .into_node()
+        }));
+
+        Fragment::new(once(
+            tag!(main[class: "bl-main-wrapper"] {
+                header {
+                    h1 {{ taxonomy.kind.title() }};
+                }
+                ul[class: "bl-terms"] {{ terms }}
+            })
+            .into_node(),
+        ))
+    }
+
+    /// The articles filed under a term, such as a tag.
+    fn term_page(&self, taxonomy: &Taxonomy, term: &Term) -> Fragment {
+        let newest: Vec<&Article> = term.articles.iter().rev().copied().collect();
+        let articles = self.article_list(&newest);
+
+        Fragment::new(once(
+            tag!(div[class: "bl-main-wrapper"] {
+                header {
+                    p {
+                        a[href: { taxonomy.kind.index_url() }] {{ taxonomy.kind.title() }};
+                    }
+                    h1 {{ term.name }};
+                }
+                {{ articles }}
+            })
+            .into_node(),
+        ))
+    }
+
+    /// The archive index, given every year with articles, oldest first.
+    fn archive_index(&self, years: &[Period]) -> Fragment {
+        let years = Fragment::new(years.iter().rev().map(|year| {
+            let months = Fragment::new(year.children.iter().rev().map(|month| {
+                tag!(section {
+                    h3 {{ format!("{} ({})", month.title, month.articles.len()) }};
+                    ul {{ archive_entries(&month.articles) }}
+                })
+                .into_node()
+            }));
+
+            tag!(section[class: "bl-archive-year"] {
+                { period_heading(year) };
+                { months }
+            })
+            .into_node()
+        }));
+
+        Fragment::new(once(
+            tag!(main[class: "bl-main-wrapper"] {
+                header {
+                    h1 {{ "Archive" }};
+                }
+                { years }
+            })
+            .into_node(),
+        ))
+    }
+
+    /// The page of a period of the archive, such as a year or a week.
+    fn archive_page(&self, page: &PeriodPage) -> Fragment {
+        let period = &page.period;
+        let (parent_url, parent_title) = &page.parent;
+        let parent = tag!(a[href: { parent_url.clone() }] {{ parent_title }});
+        let nav = period_nav(&page.prev, &page.next);
+
+        if period.children.is_empty() {
+            let newest: Vec<&Article> = period.articles.iter().rev().copied().collect();
+            let previews = self.article_list(&newest);
+
+            return Fragment::new(once(
+                tag!(div[class: "bl-main-wrapper"] {
+                    header {
+                        p {{ parent }};
+                        h1 {{ &period.title }};
+                        p {{ count(period.articles.len()) }};
+                    }
+                    { previews };
+                    { nav };
+                })
+                .into_node(),
+            ));
+        }
+
+        let children = Fragment::new(period.children.iter().rev().map(|child| {
+            tag!(section {
+                { period_heading(child) };
+                ul {{ archive_entries(&child.articles) }}
+            })
+            .into_node()
+        }));
+
+        Fragment::new(once(
+            tag!(main[class: "bl-main-wrapper"] {
+                header {
+                    p {{ parent }};
+                    h1 {{ &period.title }};
+                    p {{ count(period.articles.len()) }};
+                }
+                { children };
+                { nav };
+            })
+            .into_node(),
+        ))
+    }
+
+    fn page(&self, title: String, body: Body) -> Fragment {
+        let page = tag!(main[class: "bl-main-wrapper"] {
+            header {
+                h1 {{ title }};
+            }
+            { body.contents }
+        });
+        Fragment::new(once(page.into_node()))
+    }
+
+    /// A complete document, without the layout, sending visitors and
+    /// crawlers from an old URL to `target`, of which `canonical` is the
+    /// absolute form.
+    fn redirect(&self, site: &BlogData, target: &str, canonical: &str) -> Fragment {
+        let html = tag!(html[lang: { site.lang.clone() }] {
+            head {
+                meta[charset: "utf-8"];
+                meta[name: "robots", content: "noindex"];
+                meta[http-equiv: "refresh", content: { format!("0; url={}", target) }];
+                link[rel: "canonical", href: { canonical.to_string() }];
+                title {{ &site.title }};
+            }
+            body {
+                p {
+                    a[href: { target.to_string() }] {{ "This page has moved." }};
+                }
+            }
+        });
+        Fragment::new(once(html.into_node()))
+    }
+}
+
+/// The markup the generator always had.
+pub struct DefaultTheme;
+
+impl Theme for DefaultTheme {}
+
+fn separator() -> dolmen::Element {
+    tag!(p[class: "bl-separator", role: "presentation"] {{ "\u{25C7}" }})
+}
+
+fn draft_banner(article: &Article) -> Box<dyn Node> {
+    if article.draft {
+        tag!(p[class: "bl-draft"] {{ "Draft" }}).into_node()
+    } else {
+        Fragment::empty().into_node()
+    }
+}
+
+fn taxonomy_links(article: &Article) -> Box<dyn Node> {
+    Fragment::new(Kind::ALL.into_iter().map(|kind| {
+        let names = kind.terms(article);
+        if names.is_empty() {
+            return Fragment::empty().into_node();
+        }
+
+        let links = Fragment::new(names.iter().map(|name| {
+            tag!(li {
+                a[href: { kind.term_url(name) }] {{ *name }};
+            })
+            .into_node()
+        }));
+        tag!(ul[class: { format!("bl-{}", kind.path()) }] {{ links }}).into_node()
+    }))
+    .into_node()
+}
+
+fn count(articles: usize) -> String {
+    match articles {
+        1 => "1 article".to_string(),
+        n => format!("{} articles", n),
+    }
+}
+
+/// Name of a period, linking to its page if it has one, along with its
+/// number of articles.
+fn period_heading(period: &Period) -> Box<dyn Node> {
+    match &period.url {
+        Some(url) => tag!(h2 {
+            a[href: { url.clone() }] {{ &period.title }};
+            span {{ format!(" ({})", count(period.articles.len())) }};
+        })
+        .into_node(),
+        None => tag!(h2 {{ format!("{} ({})", period.title, count(period.articles.len())) }})
+            .into_node(),
+    }
+}
+
+/// Links to the given articles, newest first.
+fn archive_entries(articles: &[&Article]) -> Fragment {
+    Fragment::new(articles.iter().rev().map(|article| {
+        tag!(li {
+            span {{ article.date.format(&DATE_FORMAT).unwrap() }};
+            a[href: { article.url.clone() }] {{ &article.title }};
+        })
+        .into_node()
+    }))
+}
+
+fn period_nav(prev: &Option<(String, String)>, next: &Option<(String, String)>) -> Box<dyn Node> {
+    let link = |period: &Option<(String, String)>, rel: &'static str| match period {
+        Some((url, label)) => tag!(a[href: { url.clone() }, rel: {rel}] {{ label }}).into_node(),
+        None => Fragment::empty().into_node(),
+    };
+
+    tag!(nav[class: "bl-pagination"] {
+        { link(prev, "prev") };
+        { link(next, "next") };
+    })
+    .into_node()
+}
@@ -0,0 +1,90 @@
+use crate::{build, theme::Theme, BlogData, SiteArgs, Summary};
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+use std::{
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+fn rebuild(site: &SiteArgs, theme: &dyn Theme) -> anyhow::Result<Summary> {
+    let start = Instant::now();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| build(site, theme, false)))
+        .unwrap_or_else(|_| Err(anyhow::anyhow!("build panicked, see message above")));
+    match &result {
+        Ok(summary) => summary.print(start.elapsed()),
+        Err(error) => eprintln!("error: {:?}", error),
+    }
+    result
+}
+
+fn event_path(event: &DebouncedEvent) -> Option<&Path> {
+    match event {
+        DebouncedEvent::Create(path)
+        | DebouncedEvent::Write(path)
+        | DebouncedEvent::Chmod(path)
+        | DebouncedEvent::Remove(path)
+        | DebouncedEvent::Rename(_, path) => Some(path),
+        _ => None,
+    }
+}
+
+pub fn watch(
+    site: &SiteArgs,
+    theme: &dyn Theme,
+    mut on_build: impl FnMut(&anyhow::Result<Summary>),
+) -> anyhow::Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::watcher(tx, DEBOUNCE)?;
+
+    // Editors often save by replacing the file, so the configuration is
+    // watched through its parent directory and filtered by name.
+    let config = site.config_path().canonicalize()?;
+    let config_dir = config.parent().unwrap_or_else(|| Path::new("/"));
+    watcher.watch(config_dir, RecursiveMode::NonRecursive)?;
+
+    // The assets directory is only looked up once, so moving it elsewhere
+    // in the configuration requires restarting.
+    let assets = fs::read_to_string(&config)
+        .ok()
+        .and_then(|config| toml::from_str::<BlogData>(&config).ok())
+        .map(|blog_data| blog_data.assets.dir);
+
+    let mut sources: Vec<PathBuf> = Vec::new();
+    for dir in [Path::new("articles"), Path::new("pages")]
+        .into_iter()
+        .chain(assets.as_deref())
+    {
+        let path = site.root.join(dir);
+        if path.is_dir() {
+            let path = path.canonicalize()?;
+            watcher.watch(&path, RecursiveMode::Recursive)?;
+            sources.push(path);
+        }
+    }
+
+    on_build(&rebuild(site, theme));
+    println!("watching for changes, press Ctrl-C to stop");
+
+    loop {
+        match rx.recv()? {
+            DebouncedEvent::Error(error, path) => {
+                eprintln!("watch error on {:?}: {}", path, error);
+                continue;
+            }
+            DebouncedEvent::Rescan => {}
+            event => match event_path(&event) {
+                Some(path) if path == config || sources.iter().any(|dir| path.starts_with(dir)) => {
+                }
+                _ => continue,
+            },
+        }
+
+        // Coalesce whatever else arrived during the debounce window
+        while rx.try_recv().is_ok() {}
+        on_build(&rebuild(site, theme));
+    }
+}